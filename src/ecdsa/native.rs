@@ -1,3 +1,4 @@
+use ff::PrimeField;
 use group::prime::PrimeCurveAffine;
 use group::Curve;
 use halo2_proofs::arithmetic::{BaseExt, CurveAffine, Field, FieldExt};
@@ -51,3 +52,55 @@ pub fn generate_signature<E: CurveAffine>(
     };
     Ok((sig_data, pk))
 }
+
+pub fn verify_signature<E: CurveAffine>(
+    sig_data: &SigData<E::ScalarExt>,
+    pk: &E,
+    m_hash: E::ScalarExt,
+) -> bool {
+    let generator = <E as PrimeCurveAffine>::generator();
+
+    let s_inv = sig_data.s.invert();
+    if bool::from(s_inv.is_none()) {
+        return false;
+    }
+    let s_inv = s_inv.unwrap();
+
+    let u1 = m_hash * s_inv;
+    let u2 = sig_data.r * s_inv;
+    let sig_point = (generator * u1 + *pk * u2).to_affine();
+
+    let coordinates = sig_point.coordinates();
+    if bool::from(coordinates.is_none()) {
+        return false;
+    }
+    let x = coordinates.unwrap().x().clone();
+
+    // get x cordinate (E::Base) on E::Scalar
+    let x_repr = x.to_repr();
+    let mut x_bytes = [0u8; 64];
+    x_bytes[..32].copy_from_slice(x_repr.as_ref());
+    let x_bytes_on_n = <E as CurveAffine>::ScalarExt::from_bytes_wide(&x_bytes);
+
+    x_bytes_on_n == sig_data.r
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use secp256k1::Secp256k1Affine as Secp256;
+
+    #[test]
+    fn should_verify_generated_signature() {
+        let mut rng = thread_rng();
+
+        let sk = <Secp256 as CurveAffine>::ScalarExt::random(&mut rng);
+        let m_hash = <Secp256 as CurveAffine>::ScalarExt::from(4);
+        let (sig_data, pk) = generate_signature::<Secp256>(sk, m_hash).unwrap();
+
+        assert!(verify_signature(&sig_data, &pk, m_hash));
+
+        let other_hash = <Secp256 as CurveAffine>::ScalarExt::from(5);
+        assert!(!verify_signature(&sig_data, &pk, other_hash));
+    }
+}
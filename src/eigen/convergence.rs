@@ -0,0 +1,390 @@
+use super::native::{peers_root, reduce_field, EigenTrust, SCALE};
+use super::{
+	assert_normalized, assign_peer_leaf, verify_signature, EigenTrustConfig, SpongeChip5x5,
+	BIT_LEN_LIMB, NUMBER_OF_LIMBS,
+};
+use crate::ecdsa::SigData;
+use crate::hasher::Hasher;
+use crate::merkle::wrong::MerklePathChip;
+use ::ecdsa::ecdsa::EcdsaChip;
+use ecc::maingate::RegionCtx;
+use ecc::{EccConfig, GeneralEccChip};
+use halo2_proofs::arithmetic::CurveAffine;
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+use halo2_proofs::plonk::{Circuit, ConstraintSystem, Error};
+use integer::IntegerInstructions;
+use maingate::{MainGate, MainGateInstructions, Term, UnassignedValue};
+use std::marker::PhantomData;
+
+const EPOCH_INDEX: usize = 0;
+const PEERS_INDEX: usize = 1;
+/// Index of the global trust score of the first peer, the others follow in order.
+const GLOBAL_TRUST_INDEX: usize = 2;
+
+/// Proves `ITERATIONS` EigenTrust iterations over the whole signed local trust
+/// matrix, starting from the pre-trusted distribution. The peer set is the
+/// Merkle root `EigenTrustCircuit` checks membership against, with peer `j` at
+/// leaf `j`.
+#[derive(Clone)]
+pub struct EigenTrustConvergenceCircuit<
+	E: CurveAffine,
+	N: FieldExt,
+	const SIZE: usize,
+	const DEPTH: usize,
+	const ITERATIONS: usize,
+	H: Hasher<N, 5>
+> {
+	epoch: Option<N>,
+	local_trust: [[Option<N>; SIZE]; SIZE],
+	global_trust: [Option<N>; SIZE],
+	pubkeys: [Option<E>; SIZE],
+	/// `secret_commitment` of every peer, part of its `peer_leaf`.
+	commitments: [Option<N>; SIZE],
+	sigs: [Option<SigData<E::ScalarExt>>; SIZE],
+	pre_trust: [u64; SIZE],
+	alpha: u64,
+	aux_generator: Option<E>,
+	window_size: usize,
+	_marker: PhantomData<N>,
+	_hasher: PhantomData<H>,
+}
+
+impl<
+	E: CurveAffine,
+	N: FieldExt,
+	const SIZE: usize,
+	const DEPTH: usize,
+	const ITERATIONS: usize,
+	H: Hasher<N, 5>
+> EigenTrustConvergenceCircuit<E, N, SIZE, DEPTH, ITERATIONS, H> {
+	/// Every peer `i` signs its normalized row of `engine.local_trust()`, see
+	/// `SignedOpinion::message_hash`.
+	pub fn new(
+		epoch: Option<N>,
+		engine: &EigenTrust<SIZE>,
+		pubkeys: [Option<E>; SIZE],
+		commitments: [Option<N>; SIZE],
+		sigs: [Option<SigData<E::ScalarExt>>; SIZE],
+		aux_generator: Option<E>,
+	) -> Self {
+		let mut global_trust = *engine.pre_trust();
+		for _ in 0..ITERATIONS {
+			global_trust = engine.iterate(&global_trust);
+		}
+
+		Self {
+			epoch,
+			local_trust: engine.local_trust().map(|row| row.map(|c| Some(N::from(c)))),
+			global_trust: global_trust.map(|t| Some(N::from(t))),
+			pubkeys,
+			commitments,
+			sigs,
+			pre_trust: *engine.pre_trust(),
+			alpha: engine.alpha(),
+			aux_generator,
+			window_size: 2,
+			_marker: PhantomData,
+			_hasher: PhantomData,
+		}
+	}
+
+	/// Public inputs of the circuit in the order they are exposed:
+	/// `[epoch, peers, t_0, .., t_SIZE-1]`, where `peers` is the `peers_root` of
+	/// the peer set and `t_j` is the global trust of peer `j` after the last
+	/// iteration.
+	pub fn public_inputs(&self) -> Option<Vec<N>> {
+		let epoch = self.epoch?;
+		let pubkeys = self.pubkeys.try_map(|pk| pk)?;
+		let commitments = self.commitments.try_map(|c| c)?;
+		let global_trust = self.global_trust.try_map(|t| t)?;
+
+		let mut public_inputs = vec![N::zero(); GLOBAL_TRUST_INDEX + SIZE];
+		public_inputs[EPOCH_INDEX] = epoch;
+		public_inputs[PEERS_INDEX] = peers_root::<E, N, H, DEPTH>(&pubkeys, &commitments);
+		public_inputs[GLOBAL_TRUST_INDEX..].copy_from_slice(&global_trust);
+		Some(public_inputs)
+	}
+}
+
+impl<
+	E: CurveAffine,
+	N: FieldExt,
+	const SIZE: usize,
+	const DEPTH: usize,
+	const ITERATIONS: usize,
+	H: Hasher<N, 5>
+> Circuit<N> for EigenTrustConvergenceCircuit<E, N, SIZE, DEPTH, ITERATIONS, H> {
+	type Config = EigenTrustConfig;
+	type FloorPlanner = SimpleFloorPlanner;
+
+	fn without_witnesses(&self) -> Self {
+		Self {
+			epoch: None,
+			local_trust: [[None; SIZE]; SIZE],
+			global_trust: [None; SIZE],
+			pubkeys: [None; SIZE],
+			commitments: [None; SIZE],
+			sigs: [None; SIZE],
+			pre_trust: self.pre_trust,
+			alpha: self.alpha,
+			aux_generator: None,
+			window_size: self.window_size,
+			_marker: PhantomData,
+			_hasher: PhantomData,
+		}
+	}
+
+	fn configure(meta: &mut ConstraintSystem<N>) -> Self::Config {
+		EigenTrustConfig::configure::<E, N>(meta)
+	}
+
+	fn synthesize(
+		&self,
+		config: Self::Config,
+		mut layouter: impl Layouter<N>,
+	) -> Result<(), Error> {
+		let mut ecc_chip = GeneralEccChip::<E, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>::new(
+			EccConfig::new(config.range_config.clone(), config.main_gate_config.clone()),
+		);
+		let scalar_chip = ecc_chip.scalar_field_chip();
+		let main_gate = MainGate::new(config.main_gate_config.clone());
+		let trust_chip = config.trust_chip::<N>();
+
+		layouter.assign_region(
+			|| "assign_aux",
+			|mut region| {
+				let offset = &mut 0;
+				let ctx = &mut RegionCtx::new(&mut region, offset);
+
+				ecc_chip.assign_aux_generator(ctx, self.aux_generator)?;
+				ecc_chip.assign_aux(ctx, self.window_size, 1)?;
+				Ok(())
+			},
+		)?;
+
+		let (epoch, local_trust, m_hashes) = layouter.assign_region(|| "local_trust", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+
+			let unassigned_epoch = UnassignedValue::from(self.epoch);
+			let assigned_epoch = main_gate.assign_value(ctx, &unassigned_epoch)?;
+
+			let mut local_trust = Vec::new();
+			let mut m_hashes = Vec::new();
+			for (i, row) in self.local_trust.into_iter().enumerate() {
+				let unassigned_row = row.map(|c| UnassignedValue::from(c));
+				let assigned_row = unassigned_row.try_map(|c| main_gate.assign_value(ctx, &c))?;
+				assert_normalized(&main_gate, &trust_chip, ctx, &assigned_row)?;
+				// Peers can not vouch for themselves, see `EigenTrust::new`.
+				main_gate.assert_zero(ctx, &assigned_row[i])?;
+
+				// Message the peer signed, see `SignedOpinion::message_hash`.
+				let mut sponge = SpongeChip5x5::<N, H>::new(config.main_gate_config.clone());
+				sponge.update(&[assigned_epoch.clone()]);
+				sponge.update(&assigned_row);
+				let out = sponge.squeeze(ctx)?;
+
+				let unassigned_m_hash =
+					ecc_chip.new_unassigned_scalar(out.value().map(|h| reduce_field(h)));
+				let assigned_m_hash = scalar_chip.assign_integer(ctx, unassigned_m_hash)?;
+				main_gate.assert_equal(ctx, &assigned_m_hash.native(), &out)?;
+
+				local_trust.push(assigned_row);
+				m_hashes.push(assigned_m_hash);
+			}
+
+			Ok((assigned_epoch, local_trust, m_hashes))
+		})?;
+
+		let ecdsa_chip = EcdsaChip::new(ecc_chip.clone());
+
+		let assigned_pks = layouter.assign_region(|| "sigs_verify", |mut region| {
+			let offset = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, offset);
+
+			let assigned_pks = self.pubkeys.try_map(|pk| ecc_chip.assign_point(ctx, pk))?;
+			for i in 0..SIZE {
+				verify_signature(&ecc_chip, &ecdsa_chip, ctx, self.sigs[i], &assigned_pks[i], &m_hashes[i])?;
+			}
+
+			Ok(assigned_pks)
+		})?;
+
+		let peers = layouter.assign_region(|| "peers_root", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+			let mg_config = &config.main_gate_config;
+			let merkle_chip = MerklePathChip::<N, DEPTH, H>::new(mg_config.clone());
+
+			let mut leaves = Vec::new();
+			for i in 0..SIZE {
+				let commitment =
+					main_gate.assign_value(ctx, &UnassignedValue::from(self.commitments[i]))?;
+				let leaf =
+					assign_peer_leaf::<E, N, H>(mg_config, ctx, &assigned_pks[i], &commitment)?;
+				leaves.push(leaf);
+			}
+
+			merkle_chip.tree_root(ctx, &leaves)
+		})?;
+
+		let global_trust = layouter.assign_region(|| "iterations", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+
+			let mut global_trust =
+				self.pre_trust.try_map(|p| main_gate.assign_constant(ctx, N::from(p)))?;
+			for _ in 0..ITERATIONS {
+				let mut next = global_trust.clone();
+				for j in 0..SIZE {
+					let mut sum = main_gate.assign_constant(ctx, N::zero())?;
+					for i in 0..SIZE {
+						let product = main_gate.mul(ctx, &local_trust[i][j], &global_trust[i])?;
+						sum = main_gate.add(ctx, &sum, &product)?;
+					}
+
+					// `(1 - alpha) * sum + alpha * p_j`, scaled by `SCALE^2`
+					let weighted = main_gate.compose(
+						ctx,
+						&[Term::Assigned(&sum, N::from(SCALE - self.alpha))],
+						N::from(self.alpha * self.pre_trust[j] * SCALE),
+					)?;
+					let weighted = trust_chip.range_checked(ctx, &weighted)?;
+					let t_j = trust_chip.div_constant(ctx, &weighted, SCALE * SCALE)?;
+					next[j] = t_j.raw().clone();
+				}
+				global_trust = next;
+			}
+
+			Ok(global_trust)
+		})?;
+
+		layouter.constrain_instance(epoch.cell(), config.instance, EPOCH_INDEX)?;
+		layouter.constrain_instance(peers.cell(), config.instance, PEERS_INDEX)?;
+		for j in 0..SIZE {
+			layouter.constrain_instance(
+				global_trust[j].cell(),
+				config.instance,
+				GLOBAL_TRUST_INDEX + j,
+			)?;
+		}
+
+		config.config_range(&mut layouter)?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::ecdsa::native::generate_signature;
+	use crate::eigen::native::{secret_commitment, SignedOpinion};
+	use crate::poseidon::{native::Poseidon, params::bn254_5x5::Params5x5Bn254};
+	use group::{Curve, Group};
+	use halo2_proofs::arithmetic::Field;
+	use maingate::halo2::{dev::MockProver, pairing::bn256::Fr};
+	use rand::thread_rng;
+	use secp256k1::Secp256k1Affine as Secp256;
+
+	const SIZE: usize = 4;
+	const DEPTH: usize = 2;
+	const ITERATIONS: usize = 3;
+
+	type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
+
+	type TestCircuit =
+		EigenTrustConvergenceCircuit<Secp256, Fr, SIZE, DEPTH, ITERATIONS, TestPoseidon>;
+
+	fn engine() -> EigenTrust<SIZE> {
+		let local_trust = [
+			[0, 3, 1, 0],
+			[2, 0, 2, 1],
+			[1, 1, 0, 1],
+			[1, 1, 1, 0],
+		];
+		EigenTrust::<SIZE>::new(local_trust, [true, true, false, false], 100, 1, 20)
+	}
+
+	/// Every peer signs its normalized row of local trust for `signed_epoch`.
+	fn convergence_circuit(epoch: Fr, signed_epoch: Fr) -> TestCircuit {
+		let mut rng = thread_rng();
+		let engine = engine();
+
+		let mut pubkeys = [None; SIZE];
+		let mut commitments = [None; SIZE];
+		let mut sigs = [None; SIZE];
+		for i in 0..SIZE {
+			let m_hash = SignedOpinion::<Secp256, SIZE>::message_hash::<Fr, TestPoseidon>(
+				signed_epoch,
+				&engine.local_trust()[i],
+			);
+			let sk = <Secp256 as CurveAffine>::ScalarExt::random(&mut rng);
+			let (sig, pk) = generate_signature::<Secp256>(sk, m_hash).unwrap();
+			pubkeys[i] = Some(pk);
+			commitments[i] = Some(secret_commitment::<Fr, TestPoseidon>(Fr::random(&mut rng)));
+			sigs[i] = Some(sig);
+		}
+
+		let aux_generator = <Secp256 as CurveAffine>::CurveExt::random(&mut rng).to_affine();
+		TestCircuit::new(Some(epoch), &engine, pubkeys, commitments, sigs, Some(aux_generator))
+	}
+
+	#[test]
+	fn test_eigen_trust_convergence_verify() {
+		let k = 21;
+		let epoch = Fr::from(1);
+
+		let circuit = convergence_circuit(epoch, epoch);
+
+		let engine = engine();
+		let mut global_trust = *engine.pre_trust();
+		for _ in 0..ITERATIONS {
+			global_trust = engine.iterate(&global_trust);
+		}
+		let public_inputs = circuit.public_inputs().unwrap();
+		let expected: Vec<Fr> = global_trust.iter().map(|t| Fr::from(*t)).collect();
+		assert_eq!(public_inputs[GLOBAL_TRUST_INDEX..], expected[..]);
+
+		let prover = match MockProver::<Fr>::run(k, &circuit, vec![public_inputs]) {
+			Ok(prover) => prover,
+			Err(e) => panic!("{}", e),
+		};
+		assert_eq!(prover.verify(), Ok(()));
+	}
+
+	#[test]
+	fn test_eigen_trust_convergence_stale_opinions() {
+		let k = 21;
+
+		// Opinions were signed for the previous epoch.
+		let circuit = convergence_circuit(Fr::from(2), Fr::from(1));
+
+		let public_inputs = circuit.public_inputs().unwrap();
+		let prover = MockProver::<Fr>::run(k, &circuit, vec![public_inputs]).unwrap();
+		assert!(prover.verify().is_err());
+	}
+
+	#[test]
+	fn test_eigen_trust_convergence_self_trust() {
+		let k = 21;
+		let epoch = Fr::from(1);
+
+		// Peer 0 signs a normalized row that vouches for itself.
+		let mut circuit = convergence_circuit(epoch, epoch);
+		let scores = [100, 650, 250, 0];
+		let m_hash =
+			SignedOpinion::<Secp256, SIZE>::message_hash::<Fr, TestPoseidon>(epoch, &scores);
+		let sk = <Secp256 as CurveAffine>::ScalarExt::random(&mut thread_rng());
+		let (sig, pk) = generate_signature::<Secp256>(sk, m_hash).unwrap();
+		circuit.local_trust[0] = scores.map(|c| Some(Fr::from(c)));
+		circuit.pubkeys[0] = Some(pk);
+		circuit.sigs[0] = Some(sig);
+
+		let public_inputs = circuit.public_inputs().unwrap();
+		let prover = MockProver::<Fr>::run(k, &circuit, vec![public_inputs]).unwrap();
+		let failures = prover.verify().unwrap_err();
+		assert!(failures.iter().any(|f| format!("{:?}", f).contains("local_trust")));
+	}
+}
@@ -0,0 +1,874 @@
+pub mod convergence;
+pub mod native;
+
+use crate::ecdsa::SigData;
+use crate::fixed_point::{FixedPointChip, FixedPointConfig};
+use crate::merkle::native::MerkleProof;
+use crate::merkle::wrong::MerklePathChip;
+use self::native::{nullifier, peer_leaf, secret_commitment, SCALE};
+use crate::hasher::Hasher;
+use crate::poseidon::wrong::sponge::SpongeChip;
+use ::ecdsa::ecdsa::{AssignedEcdsaSig, AssignedPublicKey, EcdsaChip};
+use ecc::maingate::RegionCtx;
+use ecc::{AssignedPoint, EccConfig, GeneralEccChip};
+use group::prime::PrimeCurveAffine;
+use halo2_proofs::arithmetic::CurveAffine;
+use halo2_proofs::arithmetic::{Field, FieldExt};
+use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+use halo2_proofs::plonk::{Circuit, Column, ConstraintSystem, Error, Instance};
+use integer::{AssignedInteger, IntegerInstructions, NUMBER_OF_LOOKUP_LIMBS};
+use maingate::{AssignedCondition, AssignedValue, MainGate, MainGateConfig, RangeChip, RangeConfig, RangeInstructions};
+use maingate::UnassignedValue;
+use maingate::MainGateInstructions;
+use std::marker::PhantomData;
+
+const BIT_LEN_LIMB: usize = 68;
+const NUMBER_OF_LIMBS: usize = 4;
+const BIT_LEN_LOOKUP: usize = BIT_LEN_LIMB / NUMBER_OF_LOOKUP_LIMBS;
+/// Bit length every trust value is range checked to.
+const TRUST_BITS: usize = 32;
+
+const EPOCH_INDEX: usize = 0;
+const OP_V_INDEX: usize = 1;
+const PEERS_INDEX: usize = 2;
+const NULLIFIER_INDEX: usize = 3;
+
+type SpongeChip5x5<F, H> = SpongeChip<F, 5, H>;
+type TrustChip<F> = FixedPointChip<F, SCALE, TRUST_BITS>;
+
+#[derive(Clone, Debug)]
+pub struct EigenTrustConfig {
+	main_gate_config: MainGateConfig,
+	range_config: RangeConfig,
+	instance: Column<Instance>,
+}
+
+impl EigenTrustConfig {
+	/// Configuration shared by the EigenTrust circuits: ECDSA over `E`, trust
+	/// arithmetic with `TrustChip` and the public inputs.
+	pub fn configure<E: CurveAffine, N: FieldExt>(meta: &mut ConstraintSystem<N>) -> Self {
+		let (rns_base, rns_scalar) =
+			GeneralEccChip::<E, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>::rns();
+		let main_gate_config = MainGate::<N>::configure(meta);
+		let mut overflow_bit_lengths: Vec<usize> = vec![];
+		overflow_bit_lengths.extend(rns_base.overflow_lengths());
+		overflow_bit_lengths.extend(rns_scalar.overflow_lengths());
+		overflow_bit_lengths
+			.extend(TrustChip::<N>::overflow_lengths(BIT_LEN_LOOKUP, &[SCALE * SCALE]));
+		let range_config =
+			RangeChip::<N>::configure(meta, &main_gate_config, overflow_bit_lengths);
+		let instance = meta.instance_column();
+		meta.enable_equality(instance);
+		EigenTrustConfig {
+			main_gate_config,
+			range_config,
+			instance,
+		}
+	}
+
+	fn trust_chip<N: FieldExt>(&self) -> TrustChip<N> {
+		TrustChip::new(FixedPointConfig::new(
+			self.main_gate_config.clone(),
+			self.range_config.clone(),
+			BIT_LEN_LOOKUP,
+		))
+	}
+
+	pub fn config_range<N: FieldExt>(
+		&self,
+		layouter: &mut impl Layouter<N>,
+	) -> Result<(), Error> {
+		let range_chip = RangeChip::<N>::new(self.range_config.clone(), BIT_LEN_LOOKUP);
+		range_chip.load_limb_range_table(layouter)?;
+		range_chip.load_overflow_range_tables(layouter)?;
+
+		Ok(())
+	}
+}
+
+/// Checks local trust is non-negative and sums up to exactly `SCALE`.
+fn assert_normalized<N: FieldExt>(
+	main_gate: &MainGate<N>,
+	trust_chip: &TrustChip<N>,
+	ctx: &mut RegionCtx<'_, '_, N>,
+	local_trust: &[AssignedValue<N>],
+) -> Result<(), Error> {
+	let mut sum = main_gate.assign_constant(ctx, N::zero())?;
+	for c in local_trust {
+		trust_chip.range_checked(ctx, c)?;
+		sum = main_gate.add(ctx, &sum, c)?;
+	}
+	let diff = main_gate.add_constant(ctx, &sum, -N::from(SCALE))?;
+	main_gate.assert_zero(ctx, &diff)
+}
+
+/// `H([inputs.., 0, ..])[0]`, the in-circuit counterpart of the fixed
+/// arity hashes in `native`.
+fn assign_hash<N: FieldExt, H: Hasher<N, 5>>(
+	main_gate_config: &MainGateConfig,
+	ctx: &mut RegionCtx<'_, '_, N>,
+	inputs: &[AssignedValue<N>],
+) -> Result<AssignedValue<N>, Error> {
+	assert!(inputs.len() <= 5);
+	let main_gate = MainGate::new(main_gate_config.clone());
+	let zero = main_gate.assign_constant(ctx, N::zero())?;
+	let mut state = [(); 5].map(|_| zero.clone());
+	for (i, input) in inputs.iter().enumerate() {
+		state[i] = input.clone();
+	}
+
+	Ok(H::permute_assigned(main_gate_config, ctx, state)?[0].clone())
+}
+
+/// In-circuit counterpart of `native::peer_leaf`.
+fn assign_peer_leaf<E: CurveAffine, N: FieldExt, H: Hasher<N, 5>>(
+	main_gate_config: &MainGateConfig,
+	ctx: &mut RegionCtx<'_, '_, N>,
+	pk: &AssignedPoint<E::Base, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>,
+	commitment: &AssignedValue<N>,
+) -> Result<AssignedValue<N>, Error> {
+	let x = pk.get_x().native().clone();
+	let y = pk.get_y().native().clone();
+	assign_hash::<N, H>(main_gate_config, ctx, &[x, y, commitment.clone()])
+}
+
+/// Assigns the activity flags of the peer slots, constrained to be boolean.
+fn assign_active<N: FieldExt, const SIZE: usize>(
+	main_gate: &MainGate<N>,
+	ctx: &mut RegionCtx<'_, '_, N>,
+	active: &[Option<bool>; SIZE],
+) -> Result<[AssignedCondition<N>; SIZE], Error> {
+	active.try_map(|a| {
+		let bit = a.map(|a| if a { N::one() } else { N::zero() });
+		main_gate.assign_bit(ctx, &UnassignedValue::from(bit))
+	})
+}
+
+/// Verifies `sig` over `m_hash` against an already assigned public key.
+fn verify_signature<E: CurveAffine, N: FieldExt>(
+	ecc_chip: &GeneralEccChip<E, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>,
+	ecdsa_chip: &EcdsaChip<E, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>,
+	ctx: &mut RegionCtx<'_, '_, N>,
+	sig: Option<SigData<E::ScalarExt>>,
+	pk: &AssignedPoint<E::Base, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>,
+	m_hash: &AssignedInteger<E::ScalarExt, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>,
+) -> Result<(), Error> {
+	let scalar_chip = ecc_chip.scalar_field_chip();
+
+	let integer_r = ecc_chip.new_unassigned_scalar(sig.map(|s| s.r));
+	let integer_s = ecc_chip.new_unassigned_scalar(sig.map(|s| s.s));
+
+	let r_assigned = scalar_chip.assign_integer(ctx, integer_r)?;
+	let s_assigned = scalar_chip.assign_integer(ctx, integer_s)?;
+	let sig = AssignedEcdsaSig {
+		r: r_assigned,
+		s: s_assigned,
+	};
+
+	let pk_assigned = AssignedPublicKey {
+		point: pk.clone(),
+	};
+
+	ecdsa_chip.verify(ctx, &sig, &pk_assigned, m_hash)
+}
+
+/// `verify_signature` of a peer slot: the checks of `EcdsaChip::verify`, with
+/// the final `Q.x = r` comparison only enforced where `active` is set. Any point
+/// and non-zero `r` and `s` satisfy the other checks, so inactive slots need no
+/// key or signature.
+fn verify_slot_signature<E: CurveAffine, N: FieldExt>(
+	ecc_chip: &GeneralEccChip<E, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>,
+	ctx: &mut RegionCtx<'_, '_, N>,
+	sig: Option<SigData<E::ScalarExt>>,
+	pk: &AssignedPoint<E::Base, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>,
+	m_hash: &AssignedInteger<E::ScalarExt, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>,
+	active: &AssignedCondition<N>,
+	window_size: usize,
+) -> Result<(), Error> {
+	let scalar_chip = ecc_chip.scalar_field_chip();
+	let base_chip = ecc_chip.base_field_chip();
+
+	let integer_r = ecc_chip.new_unassigned_scalar(sig.map(|s| s.r));
+	let integer_s = ecc_chip.new_unassigned_scalar(sig.map(|s| s.s));
+	let r = scalar_chip.assign_integer(ctx, integer_r)?;
+	let s = scalar_chip.assign_integer(ctx, integer_s)?;
+	scalar_chip.assert_not_zero(ctx, &r)?;
+	scalar_chip.assert_not_zero(ctx, &s)?;
+
+	// `Q = (m_hash / s) * G + (r / s) * pk`
+	let (s_inv, _) = scalar_chip.invert(ctx, &s)?;
+	let u1 = scalar_chip.mul(ctx, m_hash, &s_inv)?;
+	let u2 = scalar_chip.mul(ctx, &r, &s_inv)?;
+	let generator = ecc_chip.assign_point(ctx, Some(E::generator()))?;
+	let g1 = ecc_chip.mul(ctx, &generator, &u1, window_size)?;
+	let g2 = ecc_chip.mul(ctx, pk, &u2, window_size)?;
+	let q = ecc_chip.add(ctx, &g1, &g2)?;
+
+	let q_x = base_chip.reduce(ctx, &q.get_x())?;
+	let q_x = scalar_chip.reduce_external(ctx, &q_x)?;
+	// Inactive slots compare `Q.x` with itself.
+	let r = scalar_chip.select(ctx, &r, &q_x, active)?;
+	scalar_chip.assert_strict_equal(ctx, &q_x, &r)
+}
+
+/// Proves `op_v` for one neighbour of a peer. `SIZE` is the maximum number of
+/// peers that send their opinion, slot `j` holds the peer at leaf `j` of the
+/// peer-set tree, so `SIZE` must be `2^DEPTH`. Slots whose `active` flag is unset
+/// are padding: they prove their leaf is empty, so no peer of the set can be
+/// left out, their signature is not checked and they contribute nothing to `t_i`
+/// and `c_v`.
+#[derive(Clone)]
+pub struct EigenTrustCircuit<
+	E: CurveAffine,
+	N: FieldExt,
+	const SIZE: usize,
+	const DEPTH: usize,
+	H: Hasher<N, 5>
+> {
+	op_v: Option<N>,
+	pubkey_i: Option<E>,
+	pubkey_v: Option<E>,
+	sig_i: Option<SigData<E::ScalarExt>>,
+	m_hash: Option<E::ScalarExt>,
+	epoch: Option<N>,
+	opinions: [Option<N>; SIZE],
+	c_v: [Option<N>; SIZE],
+	pubkeys: [Option<E>; SIZE],
+	sigs: [Option<SigData<E::ScalarExt>>; SIZE],
+	active: [Option<bool>; SIZE],
+	/// Nullifier secret of peer `i` and the `secret_commitment` of `pubkey_v`
+	/// and every key in `pubkeys`, all committed to in the peer-set leaves.
+	secret_i: Option<N>,
+	commitment_v: Option<N>,
+	commitments: [Option<N>; SIZE],
+	/// Membership proofs in the peer-set tree of `pubkey_i`, `pubkey_v` and
+	/// every slot. The path of slot `j` is fixed to leaf `j`, only its siblings
+	/// are used.
+	path_i: Option<MerkleProof<N, DEPTH>>,
+	path_v: Option<MerkleProof<N, DEPTH>>,
+	paths: [Option<MerkleProof<N, DEPTH>>; SIZE],
+	aux_generator: Option<E>,
+	window_size: usize,
+	_marker: PhantomData<N>,
+	_hasher: PhantomData<H>,
+}
+
+impl<
+	E: CurveAffine,
+	N: FieldExt,
+	const SIZE: usize,
+	const DEPTH: usize,
+	H: Hasher<N, 5>
+> EigenTrustCircuit<E, N, SIZE, DEPTH, H> {
+	/// Fails to compile for a `SIZE` other than `2^DEPTH`, every leaf of the
+	/// peer-set tree needs a slot.
+	const SLOT_PER_LEAF: () = assert!(SIZE == 1 << DEPTH, "every leaf of the peer-set tree needs a slot");
+
+	pub fn new(
+		op_v: Option<N>,
+		pubkey_i: Option<E>,
+		pubkey_v: Option<E>,
+		sig_i: Option<SigData<E::ScalarExt>>,
+		m_hash: Option<E::ScalarExt>,
+		epoch: Option<N>,
+		opinions: [Option<N>; SIZE],
+		c_v: [Option<N>; SIZE],
+		pubkeys: [Option<E>; SIZE],
+		sigs: [Option<SigData<E::ScalarExt>>; SIZE],
+		active: [Option<bool>; SIZE],
+		secret_i: Option<N>,
+		commitment_v: Option<N>,
+		commitments: [Option<N>; SIZE],
+		path_i: Option<MerkleProof<N, DEPTH>>,
+		path_v: Option<MerkleProof<N, DEPTH>>,
+		paths: [Option<MerkleProof<N, DEPTH>>; SIZE],
+		aux_generator: Option<E>
+	) -> Self {
+		let () = Self::SLOT_PER_LEAF;
+		Self {
+			op_v,
+			pubkey_i,
+			pubkey_v,
+			sig_i,
+			m_hash,
+			epoch,
+			opinions,
+			c_v,
+			pubkeys,
+			sigs,
+			active,
+			secret_i,
+			commitment_v,
+			commitments,
+			path_i,
+			path_v,
+			paths,
+			aux_generator,
+			window_size: 2,
+			_marker: PhantomData,
+			_hasher: PhantomData,
+		}
+	}
+
+	/// Public inputs of the circuit in the order they are exposed:
+	/// `[epoch, op_v, peers, nullifier]`, where `peers` is the root of the Merkle
+	/// tree over the `peer_leaf` of every peer and `nullifier` is the `nullifier`
+	/// of peer `i` in `epoch`.
+	pub fn public_inputs(&self) -> Option<Vec<N>> {
+		let epoch = self.epoch?;
+		let op_v = self.op_v?;
+		let secret_i = self.secret_i?;
+		let commitment_i = secret_commitment::<N, H>(secret_i);
+		let leaf_i = peer_leaf::<E, N, H>(&self.pubkey_i?, commitment_i);
+		let peers = self.path_i?.root::<H>(leaf_i);
+
+		let mut public_inputs = vec![N::zero(); 4];
+		public_inputs[EPOCH_INDEX] = epoch;
+		public_inputs[OP_V_INDEX] = op_v;
+		public_inputs[PEERS_INDEX] = peers;
+		public_inputs[NULLIFIER_INDEX] = nullifier::<N, H>(secret_i, epoch);
+		Some(public_inputs)
+	}
+
+	/// `value` of slot `i`, or `padding` where the slot is inactive and left
+	/// empty.
+	fn slot_witness<T: Copy>(&self, i: usize, value: Option<T>, padding: T) -> Option<T> {
+		match self.active[i] {
+			Some(false) => value.or(Some(padding)),
+			_ => value,
+		}
+	}
+}
+
+impl<
+	E: CurveAffine,
+	N: FieldExt,
+	const SIZE: usize,
+	const DEPTH: usize,
+	H: Hasher<N, 5>
+> Circuit<N> for EigenTrustCircuit<E, N, SIZE, DEPTH, H> {
+	type Config = EigenTrustConfig;
+	type FloorPlanner = SimpleFloorPlanner;
+
+	fn without_witnesses(&self) -> Self {
+		Self {
+			op_v: None,
+			pubkey_i: None,
+			pubkey_v: None,
+			sig_i: None,
+			m_hash: None,
+			epoch: None,
+			opinions: [None; SIZE],
+			c_v: [None; SIZE],
+			pubkeys: [None; SIZE],
+			sigs: [None; SIZE],
+			active: [None; SIZE],
+			secret_i: None,
+			commitment_v: None,
+			commitments: [None; SIZE],
+			path_i: None,
+			path_v: None,
+			paths: [None; SIZE],
+			aux_generator: None,
+			window_size: self.window_size,
+			_marker: PhantomData,
+			_hasher: PhantomData,
+		}
+	}
+
+	fn configure(meta: &mut ConstraintSystem<N>) -> Self::Config {
+		EigenTrustConfig::configure::<E, N>(meta)
+	}
+
+	fn synthesize(
+		&self,
+		config: Self::Config,
+		mut layouter: impl Layouter<N>,
+	) -> Result<(), Error> {
+		let () = Self::SLOT_PER_LEAF;
+		let mut ecc_chip = GeneralEccChip::<E, N, NUMBER_OF_LIMBS, BIT_LEN_LIMB>::new(
+			EccConfig::new(config.range_config.clone(), config.main_gate_config.clone()),
+		);
+		let scalar_chip = ecc_chip.scalar_field_chip();
+		let main_gate = MainGate::new(config.main_gate_config.clone());
+		let trust_chip = config.trust_chip::<N>();
+
+		layouter.assign_region(
+			|| "assign_aux",
+			|mut region| {
+				let offset = &mut 0;
+				let ctx = &mut RegionCtx::new(&mut region, offset);
+
+				ecc_chip.assign_aux_generator(ctx, self.aux_generator)?;
+				ecc_chip.assign_aux(ctx, self.window_size, 1)?;
+				Ok(())
+			},
+		)?;
+
+		let active = layouter.assign_region(|| "active", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+			assign_active(&main_gate, ctx, &self.active)
+		})?;
+
+		let t_i = layouter.assign_region(|| "t_i", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+			let unassigned_ops = self.opinions.map(|val| UnassignedValue::from(val));
+			let assigned_ops = unassigned_ops.try_map(|val| main_gate.assign_value(ctx, &val))?;
+			let assigned_ops = assigned_ops
+				.zip(active.clone())
+				.try_map(|(op, a)| main_gate.mul(ctx, &a.into(), &op))?;
+			let assigned_ops = assigned_ops.try_map(|op| trust_chip.range_checked(ctx, &op))?;
+
+			let mut sum = assigned_ops[0].clone();
+			for i in 1..SIZE {
+				sum = trust_chip.add(ctx, &sum, &assigned_ops[i])?;
+			}
+
+			Ok(sum)
+		})?;
+
+		let assigned_c_v = layouter.assign_region(|| "c_v_normalization", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+			let unassigned_c_v = self.c_v.map(|val| UnassignedValue::from(val));
+			let assigned_c_v = unassigned_c_v.try_map(|c| main_gate.assign_value(ctx, &c))?;
+			let assigned_c_v = assigned_c_v
+				.zip(active.clone())
+				.try_map(|(c, a)| main_gate.mul(ctx, &a.into(), &c))?;
+
+			assert_normalized(&main_gate, &trust_chip, ctx, &assigned_c_v)?;
+
+			Ok(assigned_c_v)
+		})?;
+
+		let (pk_i, secret_i, assigned_pks, leaf_v, leaves, peers) =
+			layouter.assign_region(|| "peers_membership", |mut region| {
+				let position = &mut 0;
+				let ctx = &mut RegionCtx::new(&mut region, position);
+				let mg_config = &config.main_gate_config;
+				let merkle_chip = MerklePathChip::<N, DEPTH, H>::new(mg_config.clone());
+
+				let pk_i = ecc_chip.assign_point(ctx, self.pubkey_i)?;
+				let secret_i = main_gate.assign_value(ctx, &UnassignedValue::from(self.secret_i))?;
+				let commitment_i = assign_hash::<N, H>(mg_config, ctx, &[secret_i])?;
+				let leaf_i = assign_peer_leaf::<E, N, H>(mg_config, ctx, &pk_i, &commitment_i)?;
+				let peers = merkle_chip.root(ctx, &leaf_i, self.path_i)?;
+
+				let pk_v = ecc_chip.assign_point(ctx, self.pubkey_v)?;
+				let commitment_v =
+					main_gate.assign_value(ctx, &UnassignedValue::from(self.commitment_v))?;
+				let leaf_v = assign_peer_leaf::<E, N, H>(mg_config, ctx, &pk_v, &commitment_v)?;
+				let root_v = merkle_chip.root(ctx, &leaf_v, self.path_v)?;
+				main_gate.assert_equal(ctx, &root_v, &peers)?;
+
+				let mut assigned_pks = Vec::new();
+				let mut leaves = Vec::new();
+				for i in 0..SIZE {
+					let pk = self.slot_witness(i, self.pubkeys[i], E::generator());
+					let pk = ecc_chip.assign_point(ctx, pk)?;
+					let commitment = self.slot_witness(i, self.commitments[i], N::zero());
+					let commitment = main_gate.assign_value(ctx, &UnassignedValue::from(commitment))?;
+					let leaf = assign_peer_leaf::<E, N, H>(mg_config, ctx, &pk, &commitment)?;
+					// Slot `i` is leaf `i` of the tree, padding slots prove it is empty.
+					let slot_leaf = main_gate.mul(ctx, &active[i].clone().into(), &leaf)?;
+					let siblings = self.paths[i].map(|p| p.siblings);
+					let root = merkle_chip.root_at(ctx, &slot_leaf, i, siblings)?;
+					main_gate.assert_equal(ctx, &root, &peers)?;
+					assigned_pks.push(pk);
+					leaves.push(leaf);
+				}
+
+				Ok((pk_i, secret_i, assigned_pks, leaf_v, leaves, peers))
+			})?;
+
+		let c_v = layouter.assign_region(|| "c_v", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+
+			let mut final_c_v = main_gate.assign_constant(ctx, N::zero())?;
+			for i in 0..SIZE {
+				let is_eq = main_gate.is_equal(ctx, &leaves[i], &leaf_v)?;
+				let product = main_gate.mul(ctx, &is_eq.into(), &assigned_c_v[i])?;
+				final_c_v = main_gate.add(ctx, &final_c_v, &product)?;
+			}
+
+			Ok(final_c_v)
+		})?;
+
+		let opv = layouter.assign_region(|| "op_v", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+			
+			let unassigned_opv = UnassignedValue::from(self.op_v);
+			let assigned_opv = main_gate.assign_value(ctx, &unassigned_opv)?;
+			let c_v = trust_chip.range_checked(ctx, &c_v)?;
+			let res = trust_chip.mul(ctx, &t_i, &c_v)?;
+			main_gate.assert_equal(ctx, &assigned_opv, res.raw())?;
+
+			Ok(res.raw().clone())
+		})?;
+
+		let (epoch, nullifier, m_hash) = layouter.assign_region(|| "m_hash", |mut region| {
+			let position = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, position);
+			let mg_config = &config.main_gate_config;
+
+			let unassigned_epoch = UnassignedValue::from(self.epoch);
+			let assigned_epoch =  main_gate.assign_value(ctx, &unassigned_epoch)?;
+			let unassigned_m_hash = ecc_chip.new_unassigned_scalar(self.m_hash);
+			let assigned_m_hash = scalar_chip.assign_integer(ctx, unassigned_m_hash)?;
+
+			// Same secret the commitment in the peer set opens to.
+			let one = main_gate.assign_constant(ctx, N::one())?;
+			let input = [secret_i.clone(), assigned_epoch.clone(), one];
+			let nullifier = assign_hash::<N, H>(mg_config, ctx, &input)?;
+
+			// Signing over the nullifier ties the signatures to one opinion of peer `i`.
+			let input = [nullifier.clone(), assigned_epoch.clone(), opv.clone()];
+			let out = assign_hash::<N, H>(mg_config, ctx, &input)?;
+			main_gate.assert_equal(ctx, &assigned_m_hash.native(), &out)?;
+
+			Ok((assigned_epoch, nullifier, assigned_m_hash))
+		})?;
+
+		let ecdsa_chip = EcdsaChip::new(ecc_chip.clone());
+
+		layouter.assign_region(
+			|| "sig_i_verify",
+			|mut region| {
+				let offset = &mut 0;
+				let ctx = &mut RegionCtx::new(&mut region, offset);
+
+				verify_signature(&ecc_chip, &ecdsa_chip, ctx, self.sig_i, &pk_i, &m_hash)
+			},
+		)?;
+
+		layouter.assign_region(|| "sigs_verify", |mut region| {
+			let offset = &mut 0;
+			let ctx = &mut RegionCtx::new(&mut region, offset);
+
+			let padding = SigData { r: E::ScalarExt::one(), s: E::ScalarExt::one() };
+			for i in 0..SIZE {
+				let sig = self.slot_witness(i, self.sigs[i], padding);
+				verify_slot_signature(
+					&ecc_chip,
+					ctx,
+					sig,
+					&assigned_pks[i],
+					&m_hash,
+					&active[i],
+					self.window_size,
+				)?;
+			}
+
+			Ok(())
+		})?;
+
+		layouter.constrain_instance(epoch.cell(), config.instance, EPOCH_INDEX)?;
+		layouter.constrain_instance(opv.cell(), config.instance, OP_V_INDEX)?;
+		layouter.constrain_instance(peers.cell(), config.instance, PEERS_INDEX)?;
+		layouter.constrain_instance(nullifier.cell(), config.instance, NULLIFIER_INDEX)?;
+
+		config.config_range(&mut layouter)?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use super::native::{circuit_message_hash, EigenTrust};
+	use crate::ecdsa::native::generate_signature;
+	use crate::fixed_point::native::FixedPoint;
+	use crate::merkle::native::MerkleTree;
+	use crate::poseidon::native::Poseidon;
+	use crate::poseidon::params::bn254_5x5::Params5x5Bn254;
+	use halo2_proofs::arithmetic::CurveAffine;
+	use group::{Group, Curve};
+	use rand::thread_rng;
+	use secp256k1::Secp256k1Affine as Secp256;
+	use halo2_proofs::arithmetic::Field;
+	use maingate::halo2::{
+		dev::MockProver,
+		pairing::bn256::Fr,
+	};
+
+	const SIZE: usize = 4;
+	const DEPTH: usize = 2;
+
+	type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
+
+	/// Builds the circuit of the peer in slot `i` for neighbour `v`. Every active
+	/// peer signs the `op_v` message and slot `j` holds the leaf of peer `j` of the
+	/// peer-set tree, inactive slots are left empty and their leaf is zero.
+	fn eigen_trust_circuit(
+		epoch: Fr,
+		opinions: [Fr; SIZE],
+		c_v: [Fr; SIZE],
+		op_v: Fr,
+		i: usize,
+		v: usize,
+		active: [bool; SIZE],
+	) -> EigenTrustCircuit<Secp256, Fr, SIZE, DEPTH, TestPoseidon> {
+		assert!(active[i] && active[v]);
+		let mut rng = thread_rng();
+
+		let secrets = [(); SIZE].map(|_| Fr::random(&mut rng));
+		let m_hash = circuit_message_hash::<Secp256, Fr, TestPoseidon>(secrets[i], epoch, op_v);
+
+		let mut pubkeys = [None; SIZE];
+		let mut sigs = [None; SIZE];
+		let mut commitments = [None; SIZE];
+		let mut leaves = [Fr::zero(); SIZE];
+		for j in 0..SIZE {
+			if !active[j] {
+				continue;
+			}
+			let sk = <Secp256 as CurveAffine>::ScalarExt::random(&mut rng);
+			let (sig, pk) = generate_signature::<Secp256>(sk, m_hash).unwrap();
+			let commitment = secret_commitment::<Fr, TestPoseidon>(secrets[j]);
+			pubkeys[j] = Some(pk);
+			sigs[j] = Some(sig);
+			commitments[j] = Some(commitment);
+			leaves[j] = peer_leaf::<_, Fr, TestPoseidon>(&pk, commitment);
+		}
+		let tree = MerkleTree::<Fr, DEPTH, TestPoseidon>::new(&leaves);
+		let mut paths = [None; SIZE];
+		for j in 0..SIZE {
+			paths[j] = Some(tree.proof(j));
+		}
+
+		let aux_generator = <Secp256 as CurveAffine>::CurveExt::random(&mut rng).to_affine();
+		EigenTrustCircuit::new(
+			Some(op_v),
+			pubkeys[i],
+			pubkeys[v],
+			sigs[i],
+			Some(m_hash),
+			Some(epoch),
+			opinions.map(|op| Some(op)),
+			c_v.map(|c| Some(c)),
+			pubkeys,
+			sigs,
+			active.map(|a| Some(a)),
+			Some(secrets[i]),
+			commitments[v],
+			commitments,
+			paths[i],
+			paths[v],
+			paths,
+			Some(aux_generator),
+		)
+	}
+
+	#[test]
+	fn test_eigen_trust_verify() {
+		let k = 21;
+
+		let epoch = Fr::from(1);
+		let local_trust = [
+			[0, 3, 1, 0],
+			[2, 0, 2, 1],
+			[1, 1, 0, 1],
+			[1, 1, 1, 0],
+		];
+		let engine = EigenTrust::<SIZE>::new(local_trust, [true, true, false, false], 100, 1, 20);
+		let global_trust = engine.converge();
+
+		// Peer `i` is the prover, neighbour `v` is the peer at index 2 of the peer set.
+		let i = 1;
+		let v = 2;
+		let (opinions, c_v) = engine.circuit_inputs::<Fr>(&global_trust, i);
+		let op_v = engine.op_v::<Fr>(&global_trust, i, v);
+
+		let eigen_trust = eigen_trust_circuit(epoch, opinions, c_v, op_v, i, v, [true; SIZE]);
+
+		let public_inputs = vec![eigen_trust.public_inputs().unwrap()];
+		let prover = match MockProver::<Fr>::run(k, &eigen_trust, public_inputs) {
+			Ok(prover) => prover,
+			Err(e) => panic!("{}", e),
+		};
+		assert_eq!(prover.verify(), Ok(()));
+	}
+
+	#[test]
+	fn test_eigen_trust_engine_message() {
+		let k = 21;
+
+		let epoch = Fr::from(1);
+		let local_trust = [
+			[0, 3, 1, 0],
+			[2, 0, 2, 1],
+			[1, 1, 0, 1],
+			[1, 1, 1, 0],
+		];
+		let engine = EigenTrust::<SIZE>::new(local_trust, [true, true, false, false], 100, 1, 20);
+		let global_trust = engine.converge();
+
+		let i = 0;
+		let v = 1;
+		let (opinions, c_v) = engine.circuit_inputs::<Fr>(&global_trust, i);
+		let op_v = engine.op_v::<Fr>(&global_trust, i, v);
+		let eigen_trust = eigen_trust_circuit(epoch, opinions, c_v, op_v, i, v, [true; SIZE]);
+
+		// The engine gives the `op_v` and `m_hash` the circuit is signed over.
+		let secret_i = eigen_trust.secret_i.unwrap();
+		let (op_v, m_hash) =
+			engine.circuit_message::<Secp256, Fr, TestPoseidon>(&global_trust, i, v, secret_i, epoch);
+		assert_eq!(eigen_trust.op_v, Some(op_v));
+		assert_eq!(eigen_trust.m_hash, Some(m_hash));
+
+		let public_inputs = vec![eigen_trust.public_inputs().unwrap()];
+		let prover = MockProver::<Fr>::run(k, &eigen_trust, public_inputs).unwrap();
+		assert_eq!(prover.verify(), Ok(()));
+	}
+
+	#[test]
+	fn test_eigen_trust_wrong_nullifier() {
+		let k = 21;
+
+		let epoch = Fr::from(1);
+		let opinions = [250, 250, 250, 250].map(|op| Fr::from(op));
+		let c_v = [250, 250, 250, 250].map(|c| Fr::from(c));
+
+		let i = 0;
+		let v = 1;
+		let op_v = (FixedPoint::<SCALE>::from_raw(1000) * FixedPoint::from_raw(250)).to_field();
+
+		let eigen_trust = eigen_trust_circuit(epoch, opinions, c_v, op_v, i, v, [true; SIZE]);
+
+		// The nullifier of the same peer in another epoch.
+		let mut public_inputs = eigen_trust.public_inputs().unwrap();
+		let secret_i = eigen_trust.secret_i.unwrap();
+		public_inputs[NULLIFIER_INDEX] = nullifier::<Fr, TestPoseidon>(secret_i, Fr::from(2));
+
+		let prover = MockProver::<Fr>::run(k, &eigen_trust, vec![public_inputs]).unwrap();
+		assert!(prover.verify().is_err());
+	}
+
+	#[test]
+	fn test_eigen_trust_padding_slots() {
+		let k = 21;
+
+		let epoch = Fr::from(1);
+		// Values in the inactive slot are ignored, they are not part of `t_i` or the
+		// normalization of `c_v`.
+		let opinions = [400, 300, 999, 300].map(|op| Fr::from(op));
+		let c_v = [500, 0, 777, 500].map(|c| Fr::from(c));
+		let active = [true, true, false, true];
+
+		let i = 1;
+		let v = 0;
+		let op_v = (FixedPoint::<SCALE>::from_raw(1000) * FixedPoint::from_raw(500)).to_field();
+
+		let eigen_trust = eigen_trust_circuit(epoch, opinions, c_v, op_v, i, v, active);
+
+		let public_inputs = vec![eigen_trust.public_inputs().unwrap()];
+		let prover = MockProver::<Fr>::run(k, &eigen_trust, public_inputs).unwrap();
+		assert_eq!(prover.verify(), Ok(()));
+	}
+
+	#[test]
+	fn test_eigen_trust_hidden_peer() {
+		let k = 21;
+
+		let epoch = Fr::from(1);
+		let opinions = [400, 300, 0, 300].map(|op| Fr::from(op));
+		let c_v = [500, 0, 0, 500].map(|c| Fr::from(c));
+
+		let i = 1;
+		let v = 0;
+		let op_v = (FixedPoint::<SCALE>::from_raw(1000) * FixedPoint::from_raw(500)).to_field();
+
+		// The third peer is part of the peer set, but its slot is flagged inactive to
+		// drop its opinion.
+		let mut eigen_trust = eigen_trust_circuit(epoch, opinions, c_v, op_v, i, v, [true; SIZE]);
+		eigen_trust.active[2] = Some(false);
+
+		let public_inputs = vec![eigen_trust.public_inputs().unwrap()];
+		let prover = MockProver::<Fr>::run(k, &eigen_trust, public_inputs).unwrap();
+		let failures = prover.verify().unwrap_err();
+		assert!(failures.iter().any(|f| format!("{:?}", f).contains("peers_membership")));
+	}
+
+	#[test]
+	fn test_eigen_trust_signer_outside_peer_set() {
+		let k = 21;
+
+		let epoch = Fr::from(1);
+		let opinions = [250, 250, 250, 250].map(|op| Fr::from(op));
+		let c_v = [250, 250, 250, 250].map(|c| Fr::from(c));
+
+		let i = 2;
+		let v = 1;
+		let op_v = (FixedPoint::<SCALE>::from_raw(1000) * FixedPoint::from_raw(250)).to_field();
+
+		let mut eigen_trust = eigen_trust_circuit(epoch, opinions, c_v, op_v, i, v, [true; SIZE]);
+		// The first signer is swapped for a key that signs the same message, but was
+		// never added to the peer set.
+		let mut rng = thread_rng();
+		let sk = <Secp256 as CurveAffine>::ScalarExt::random(&mut rng);
+		let (sig, pk) = generate_signature::<Secp256>(sk, eigen_trust.m_hash.unwrap()).unwrap();
+		eigen_trust.pubkeys[0] = Some(pk);
+		eigen_trust.sigs[0] = Some(sig);
+
+		let public_inputs = vec![eigen_trust.public_inputs().unwrap()];
+		let prover = MockProver::<Fr>::run(k, &eigen_trust, public_inputs).unwrap();
+		let failures = prover.verify().unwrap_err();
+		assert!(failures.iter().any(|f| format!("{:?}", f).contains("peers_membership")));
+	}
+
+	#[test]
+	fn test_eigen_trust_unnormalized_opinions() {
+		let k = 21;
+
+		let epoch = Fr::from(1);
+		let opinions = [250, 250, 250, 250].map(|op| FixedPoint::<SCALE>::from_raw(op));
+		// Local trust sums up to more than `SCALE`.
+		let c_v = [0, 600, 600, 0].map(|c| FixedPoint::<SCALE>::from_raw(c));
+
+		let v = 1;
+		let t_i = opinions.iter().fold(FixedPoint::default(), |acc, op| acc + *op);
+		let op_v = (t_i * c_v[v]).to_field();
+
+		let eigen_trust = eigen_trust_circuit(
+			epoch,
+			opinions.map(|op| op.to_field()),
+			c_v.map(|c| c.to_field()),
+			op_v,
+			0,
+			v,
+			[true; SIZE],
+		);
+
+		let public_inputs = vec![eigen_trust.public_inputs().unwrap()];
+		let prover = MockProver::<Fr>::run(k, &eigen_trust, public_inputs).unwrap();
+		let failures = prover.verify().unwrap_err();
+		assert!(failures.iter().any(|f| format!("{:?}", f).contains("c_v_normalization")));
+	}
+
+	#[test]
+	fn test_eigen_trust_negative_opinion() {
+		let k = 21;
+
+		let epoch = Fr::from(1);
+		let opinions = [250, 250, 250, 250].map(|op| Fr::from(op));
+		// Sums up to `SCALE`, but holds a negative entry.
+		let c_v = [Fr::from(SCALE + 1), -Fr::one(), Fr::zero(), Fr::zero()];
+
+		let i = 1;
+		let v = 0;
+		let t_i = FixedPoint::<SCALE>::from_raw(1000);
+		let op_v = (t_i * FixedPoint::from_raw(SCALE + 1)).to_field();
+
+		let eigen_trust = eigen_trust_circuit(epoch, opinions, c_v, op_v, i, v, [true; SIZE]);
+
+		let public_inputs = vec![eigen_trust.public_inputs().unwrap()];
+		let prover = MockProver::<Fr>::run(k, &eigen_trust, public_inputs).unwrap();
+		let failures = prover.verify().unwrap_err();
+		assert!(failures.iter().any(|f| format!("{:?}", f).contains("c_v_normalization")));
+	}
+}
@@ -0,0 +1,472 @@
+use crate::ecdsa::native::verify_signature;
+use crate::ecdsa::SigData;
+use crate::fixed_point::native::FixedPoint;
+use crate::hasher::Hasher;
+use crate::merkle::native::MerkleTree;
+use crate::poseidon::native::sponge::Sponge;
+use ff::PrimeField;
+use halo2_proofs::arithmetic::{CurveAffine, FieldExt};
+use std::collections::HashSet;
+
+/// Fixed-point scale of the normalized local trust and the global trust scores.
+/// Every normalized row of the local trust matrix sums to exactly `SCALE`.
+pub const SCALE: u64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EigenTrustError {
+	/// The signature of the opinion at this index does not verify.
+	InvalidSignature(usize),
+	/// The nullifier was already used in its epoch.
+	DuplicateNullifier,
+}
+
+/// Reinterprets a field element as an element of another field, reducing it
+/// modulo the target field's order.
+pub fn reduce_field<A: FieldExt, B: FieldExt>(a: &A) -> B {
+	let repr = a.to_repr();
+	let repr = repr.as_ref();
+	let mut bytes_wide = [0u8; 64];
+	bytes_wide[..repr.len()].copy_from_slice(repr);
+	B::from_bytes_wide(&bytes_wide)
+}
+
+/// Commitment to a peer's nullifier secret: `H([secret, 0, 0, 0, 0])[0]`.
+pub fn secret_commitment<N: FieldExt, H: Hasher<N, 5>>(secret: N) -> N {
+	let inputs = [secret, N::zero(), N::zero(), N::zero(), N::zero()];
+	H::permute(inputs)[0]
+}
+
+/// Nullifier of a peer's opinion in `epoch`: `H([secret, epoch, 1, 0, 0])[0]`.
+/// It is derived from the secret rather than the public `secret_commitment`, so
+/// only the peer can compute it, the third lane keeps it apart from the
+/// commitment.
+pub fn nullifier<N: FieldExt, H: Hasher<N, 5>>(secret: N, epoch: N) -> N {
+	let inputs = [secret, epoch, N::one(), N::zero(), N::zero()];
+	H::permute(inputs)[0]
+}
+
+/// Leaf of a peer in the peer-set Merkle tree: `H([x, y, commitment, 0, 0])[0]`
+/// over the reduced coordinates of its public key and its `secret_commitment`.
+pub fn peer_leaf<E: CurveAffine, N: FieldExt, H: Hasher<N, 5>>(pk: &E, commitment: N) -> N {
+	let coordinates = pk.coordinates().unwrap();
+	let x = reduce_field(coordinates.x());
+	let y = reduce_field(coordinates.y());
+	let inputs = [x, y, commitment, N::zero(), N::zero()];
+	H::permute(inputs)[0]
+}
+
+/// Root of the peer-set Merkle tree the EigenTrust circuits are checked against:
+/// leaf `j` is the `peer_leaf` of peer `j` and the leaves past the last peer are
+/// zero.
+pub fn peers_root<E: CurveAffine, N: FieldExt, H: Hasher<N, 5>, const DEPTH: usize>(
+	pubkeys: &[E],
+	commitments: &[N],
+) -> N {
+	assert_eq!(pubkeys.len(), commitments.len());
+	let leaves: Vec<N> = pubkeys
+		.iter()
+		.zip(commitments)
+		.map(|(pk, commitment)| peer_leaf::<E, N, H>(pk, *commitment))
+		.collect();
+	MerkleTree::<N, DEPTH, H>::new(&leaves).root()
+}
+
+/// Message every signature of an `EigenTrustCircuit` is over:
+/// `H([nullifier, epoch, op_v, 0, 0])[0]` with the `nullifier` of `secret` in
+/// `epoch`, reduced to the scalar field of `E`.
+pub fn circuit_message_hash<E: CurveAffine, N: FieldExt, H: Hasher<N, 5>>(
+	secret: N,
+	epoch: N,
+	op_v: N,
+) -> E::ScalarExt {
+	let nullifier = nullifier::<N, H>(secret, epoch);
+	let inputs = [nullifier, epoch, op_v, N::zero(), N::zero()];
+	reduce_field(&H::permute(inputs)[0])
+}
+
+/// Nullifiers of the accepted `EigenTrustCircuit` proofs. A peer gets exactly one
+/// nullifier per epoch, so a second opinion or a replayed one is rejected.
+#[derive(Default)]
+pub struct NullifierRegistry {
+	nullifiers: HashSet<Vec<u8>>,
+}
+
+impl NullifierRegistry {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn contains<N: FieldExt>(&self, nullifier: &N) -> bool {
+		self.nullifiers.contains(nullifier.to_repr().as_ref())
+	}
+
+	pub fn insert<N: FieldExt>(&mut self, nullifier: N) -> Result<(), EigenTrustError> {
+		if !self.nullifiers.insert(nullifier.to_repr().as_ref().to_vec()) {
+			return Err(EigenTrustError::DuplicateNullifier);
+		}
+		Ok(())
+	}
+}
+
+/// A peer's row of the local trust matrix, signed by the peer.
+#[derive(Clone, Copy)]
+pub struct SignedOpinion<E: CurveAffine, const SIZE: usize> {
+	pub pk: E,
+	pub sig: SigData<E::ScalarExt>,
+	pub scores: [u64; SIZE],
+}
+
+impl<E: CurveAffine, const SIZE: usize> SignedOpinion<E, SIZE> {
+	/// Message the peer signs: sponge hash of `[epoch, scores..]`. The
+	/// `EigenTrustCircuit` signatures are over `circuit_message_hash` instead.
+	pub fn message_hash<N: FieldExt, H: Hasher<N, 5>>(
+		epoch: N,
+		scores: &[u64; SIZE],
+	) -> E::ScalarExt {
+		let mut sponge = Sponge::<N, 5, H>::new();
+		sponge.update(&[epoch]);
+		sponge.update(&scores.map(|score| N::from(score)));
+		reduce_field(&sponge.squeeze())
+	}
+
+	pub fn verify<N: FieldExt, H: Hasher<N, 5>>(&self, epoch: N) -> bool {
+		let m_hash = Self::message_hash::<N, H>(epoch, &self.scores);
+		verify_signature(&self.sig, &self.pk, m_hash)
+	}
+}
+
+/// Checks the signature of every row and returns the raw local trust matrix.
+pub fn verify_opinions<E: CurveAffine, N: FieldExt, H: Hasher<N, 5>, const SIZE: usize>(
+	epoch: N,
+	opinions: &[SignedOpinion<E, SIZE>; SIZE],
+) -> Result<[[u64; SIZE]; SIZE], EigenTrustError> {
+	let mut local_trust = [[0; SIZE]; SIZE];
+	for i in 0..SIZE {
+		if !opinions[i].verify::<N, H>(epoch) {
+			return Err(EigenTrustError::InvalidSignature(i));
+		}
+		local_trust[i] = opinions[i].scores;
+	}
+	Ok(local_trust)
+}
+
+/// Scales `weights` so they sum to exactly `SCALE`, handing the rounding
+/// remainder to the entries with the largest fractional parts.
+fn distribute<const SIZE: usize>(weights: &[u64; SIZE]) -> [u64; SIZE] {
+	let total: u128 = weights.iter().map(|&w| w as u128).sum();
+	assert!(total > 0);
+
+	let mut scaled = [0; SIZE];
+	let mut remainders = [(0u128, 0usize); SIZE];
+	for j in 0..SIZE {
+		let product = weights[j] as u128 * SCALE as u128;
+		scaled[j] = (product / total) as u64;
+		remainders[j] = (product % total, j);
+	}
+
+	let deficit = SCALE - scaled.iter().sum::<u64>();
+	remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+	for k in 0..deficit as usize {
+		scaled[remainders[k].1] += 1;
+	}
+	scaled
+}
+
+/// Reference EigenTrust algorithm over fixed-point integers.
+pub struct EigenTrust<const SIZE: usize> {
+	local_trust: [[u64; SIZE]; SIZE],
+	pre_trust: [u64; SIZE],
+	alpha: u64,
+	threshold: u64,
+	max_iterations: usize,
+}
+
+impl<const SIZE: usize> EigenTrust<SIZE> {
+	/// `alpha` is the weight of the pre-trusted distribution and `threshold` the
+	/// largest per-peer change that counts as converged, both scaled by `SCALE`.
+	pub fn new(
+		local_trust: [[u64; SIZE]; SIZE],
+		pre_trusted: [bool; SIZE],
+		alpha: u64,
+		threshold: u64,
+		max_iterations: usize,
+	) -> Self {
+		assert!(alpha <= SCALE);
+
+		// Without pre-trusted peers fall back to the uniform distribution.
+		let weights = if pre_trusted.contains(&true) {
+			pre_trusted.map(|p| p as u64)
+		} else {
+			[1; SIZE]
+		};
+		let pre_trust = distribute(&weights);
+
+		let mut normalized = [[0; SIZE]; SIZE];
+		for i in 0..SIZE {
+			let mut row = local_trust[i];
+			// Peers can not vouch for themselves.
+			row[i] = 0;
+			if row.iter().all(|&score| score == 0) {
+				// Peers that trust nobody trust the other pre-trusted peers, or
+				// every other peer when they are the only pre-trusted one.
+				row = weights;
+				row[i] = 0;
+				if row.iter().all(|&weight| weight == 0) {
+					row = [1; SIZE];
+					row[i] = 0;
+				}
+			}
+			normalized[i] = distribute(&row);
+		}
+
+		Self {
+			local_trust: normalized,
+			pre_trust,
+			alpha,
+			threshold,
+			max_iterations,
+		}
+	}
+
+	/// Normalized local trust matrix, `c_ij` is at `[i][j]`.
+	pub fn local_trust(&self) -> &[[u64; SIZE]; SIZE] {
+		&self.local_trust
+	}
+
+	pub fn pre_trust(&self) -> &[u64; SIZE] {
+		&self.pre_trust
+	}
+
+	pub fn alpha(&self) -> u64 {
+		self.alpha
+	}
+
+	/// One step of `t' = (1 - alpha) * C^T * t + alpha * p`.
+	pub fn iterate(&self, global_trust: &[u64; SIZE]) -> [u64; SIZE] {
+		let scale = SCALE as u128;
+		let alpha = self.alpha as u128;
+
+		let mut next = [0; SIZE];
+		for j in 0..SIZE {
+			let mut sum = 0u128;
+			for i in 0..SIZE {
+				sum += self.local_trust[i][j] as u128 * global_trust[i] as u128;
+			}
+			let weighted = (scale - alpha) * sum + alpha * self.pre_trust[j] as u128 * scale;
+			next[j] = (weighted / (scale * scale)) as u64;
+		}
+		next
+	}
+
+	/// Runs power iteration from the pre-trusted distribution until no score
+	/// changes by more than the threshold or `max_iterations` is reached.
+	pub fn converge(&self) -> [u64; SIZE] {
+		let mut global_trust = self.pre_trust;
+		for _ in 0..self.max_iterations {
+			let next = self.iterate(&global_trust);
+			let delta = next
+				.iter()
+				.zip(global_trust.iter())
+				.map(|(a, b)| a.abs_diff(*b))
+				.max()
+				.unwrap_or(0);
+			global_trust = next;
+			if delta <= self.threshold {
+				break;
+			}
+		}
+		global_trust
+	}
+
+	/// Trust peer `j` passes to peer `i`, `c_ji * t_j`.
+	pub fn opinion(&self, global_trust: &[u64; SIZE], j: usize, i: usize) -> FixedPoint<SCALE> {
+		FixedPoint::from_raw(self.local_trust[j][i]) * FixedPoint::from_raw(global_trust[j])
+	}
+
+	/// Witness for the `EigenTrustCircuit` of peer `i`: `opinions[j]` is the trust
+	/// peer `j` passes to `i` and `c_v[j]` is `i`'s local trust in `j`.
+	pub fn circuit_inputs<N: FieldExt>(
+		&self,
+		global_trust: &[u64; SIZE],
+		i: usize,
+	) -> ([N; SIZE], [N; SIZE]) {
+		let mut opinions = [N::zero(); SIZE];
+		let mut c_v = [N::zero(); SIZE];
+		for j in 0..SIZE {
+			opinions[j] = self.opinion(global_trust, j, i).to_field();
+			c_v[j] = N::from(self.local_trust[i][j]);
+		}
+		(opinions, c_v)
+	}
+
+	/// `op_v` the `EigenTrustCircuit` of peer `i` proves for neighbour `v`, `t_i * c_iv`.
+	pub fn op_v<N: FieldExt>(&self, global_trust: &[u64; SIZE], i: usize, v: usize) -> N {
+		let mut t_i = FixedPoint::default();
+		for j in 0..SIZE {
+			t_i = t_i + self.opinion(global_trust, j, i);
+		}
+		(t_i * FixedPoint::from_raw(self.local_trust[i][v])).to_field()
+	}
+
+	/// `op_v` and `m_hash` of the `EigenTrustCircuit` of peer `i` for neighbour
+	/// `v` in `epoch`, where `secret` is the nullifier secret of peer `i`.
+	pub fn circuit_message<E: CurveAffine, N: FieldExt, H: Hasher<N, 5>>(
+		&self,
+		global_trust: &[u64; SIZE],
+		i: usize,
+		v: usize,
+		secret: N,
+		epoch: N,
+	) -> (N, E::ScalarExt) {
+		let op_v = self.op_v(global_trust, i, v);
+		(op_v, circuit_message_hash::<E, N, H>(secret, epoch, op_v))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::ecdsa::native::generate_signature;
+	use crate::poseidon::{native::Poseidon, params::bn254_5x5::Params5x5Bn254};
+	use halo2_proofs::arithmetic::Field;
+	use maingate::halo2::pairing::bn256::Fr;
+	use rand::thread_rng;
+	use secp256k1::Secp256k1Affine as Secp256;
+
+	const SIZE: usize = 4;
+	const PRE_TRUSTED: [bool; SIZE] = [true, true, false, false];
+
+	type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
+
+	fn local_trust() -> [[u64; SIZE]; SIZE] {
+		[
+			[0, 3, 1, 0],
+			[2, 0, 2, 1],
+			[1, 1, 0, 1],
+			[0, 0, 0, 0],
+		]
+	}
+
+	#[test]
+	fn should_normalize_rows_to_scale() {
+		let eigen_trust = EigenTrust::<SIZE>::new(local_trust(), PRE_TRUSTED, 100, 1, 20);
+
+		for i in 0..SIZE {
+			assert_eq!(eigen_trust.local_trust()[i][i], 0);
+			assert_eq!(eigen_trust.local_trust()[i].iter().sum::<u64>(), SCALE);
+		}
+		assert_eq!(eigen_trust.local_trust()[0], [0, 750, 250, 0]);
+		assert_eq!(eigen_trust.local_trust()[1], [400, 0, 400, 200]);
+		assert_eq!(eigen_trust.local_trust()[2], [334, 333, 0, 333]);
+		// The last peer trusts nobody, so it trusts the pre-trusted peers.
+		assert_eq!(eigen_trust.local_trust()[3], [500, 500, 0, 0]);
+	}
+
+	#[test]
+	fn should_not_self_trust_with_empty_row() {
+		// Pre-trusted peer 0 trusts nobody.
+		let mut local_trust = local_trust();
+		local_trust[0] = [0; SIZE];
+		let eigen_trust = EigenTrust::<SIZE>::new(local_trust, PRE_TRUSTED, 100, 1, 20);
+		assert_eq!(eigen_trust.local_trust()[0], [0, 1000, 0, 0]);
+		assert_eq!(eigen_trust.local_trust()[3], [500, 500, 0, 0]);
+
+		// Peer 0 is the only pre-trusted peer.
+		let pre_trusted = [true, false, false, false];
+		let eigen_trust = EigenTrust::<SIZE>::new(local_trust, pre_trusted, 100, 1, 20);
+		assert_eq!(eigen_trust.local_trust()[0], [0, 334, 333, 333]);
+		assert_eq!(eigen_trust.local_trust()[3], [1000, 0, 0, 0]);
+	}
+
+	#[test]
+	fn should_keep_uniform_trust_on_ring() {
+		// Every peer trusts only the next one.
+		let local_trust = [
+			[0, 1, 0, 0],
+			[0, 0, 1, 0],
+			[0, 0, 0, 1],
+			[1, 0, 0, 0],
+		];
+		let eigen_trust = EigenTrust::<SIZE>::new(local_trust, [false; SIZE], 100, 0, 10);
+
+		assert_eq!(eigen_trust.converge(), [250; SIZE]);
+	}
+
+	#[test]
+	fn should_converge_within_threshold() {
+		let eigen_trust = EigenTrust::<SIZE>::new(local_trust(), PRE_TRUSTED, 100, 1, 100);
+		let global_trust = eigen_trust.converge();
+		let next = eigen_trust.iterate(&global_trust);
+
+		for j in 0..SIZE {
+			assert!(global_trust[j].abs_diff(next[j]) <= 1);
+		}
+		// Integer division only ever loses a little of the total trust.
+		let total: u64 = global_trust.iter().sum();
+		assert!(total <= SCALE && total >= SCALE - SIZE as u64);
+	}
+
+	#[test]
+	fn should_produce_circuit_inputs() {
+		let eigen_trust = EigenTrust::<SIZE>::new(local_trust(), PRE_TRUSTED, 100, 1, 100);
+		let global_trust = eigen_trust.converge();
+
+		let i = 1;
+		let (opinions, c_v) = eigen_trust.circuit_inputs::<Fr>(&global_trust, i);
+
+		let mut t_i = 0u64;
+		for j in 0..SIZE {
+			t_i += eigen_trust.local_trust()[j][i] * global_trust[j] / SCALE;
+			assert_eq!(c_v[j], Fr::from(eigen_trust.local_trust()[i][j]));
+		}
+		let sum = opinions.iter().fold(Fr::zero(), |acc, op| acc + op);
+		assert_eq!(sum, Fr::from(t_i));
+
+		let v = 2;
+		let op_v = t_i * eigen_trust.local_trust()[i][v] / SCALE;
+		assert_eq!(eigen_trust.op_v::<Fr>(&global_trust, i, v), Fr::from(op_v));
+	}
+
+	#[test]
+	fn should_reject_invalid_signature() {
+		let mut rng = thread_rng();
+		let epoch = Fr::from(1);
+
+		let mut opinions = Vec::new();
+		for scores in local_trust() {
+			let m_hash =
+				SignedOpinion::<Secp256, SIZE>::message_hash::<Fr, TestPoseidon>(epoch, &scores);
+			let sk = <Secp256 as CurveAffine>::ScalarExt::random(&mut rng);
+			let (sig, pk) = generate_signature::<Secp256>(sk, m_hash).unwrap();
+			opinions.push(SignedOpinion { pk, sig, scores });
+		}
+		let mut opinions: [SignedOpinion<Secp256, SIZE>; SIZE] = opinions.try_into().ok().unwrap();
+
+		let res = verify_opinions::<_, _, TestPoseidon, SIZE>(epoch, &opinions);
+		assert_eq!(res, Ok(local_trust()));
+
+		// Peer 2 claims a different row than the one it signed.
+		opinions[2].scores[0] += 1;
+		let res = verify_opinions::<_, _, TestPoseidon, SIZE>(epoch, &opinions);
+		assert_eq!(res, Err(EigenTrustError::InvalidSignature(2)));
+	}
+
+	#[test]
+	fn should_reject_duplicate_nullifier() {
+		let secret = Fr::from(42);
+		let nullifier_1 = nullifier::<Fr, TestPoseidon>(secret, Fr::from(1));
+		let nullifier_2 = nullifier::<Fr, TestPoseidon>(secret, Fr::from(2));
+		assert_ne!(nullifier_1, nullifier_2);
+		// The commitment is public, the nullifier can not be computed from it.
+		let commitment = secret_commitment::<Fr, TestPoseidon>(secret);
+		assert_ne!(nullifier::<Fr, TestPoseidon>(commitment, Fr::from(1)), nullifier_1);
+
+		let mut registry = NullifierRegistry::new();
+		assert_eq!(registry.insert(nullifier_1), Ok(()));
+		assert_eq!(registry.insert(nullifier_2), Ok(()));
+		assert!(registry.contains(&nullifier_1));
+
+		// A second opinion of the same peer in epoch 1.
+		assert_eq!(registry.insert(nullifier_1), Err(EigenTrustError::DuplicateNullifier));
+	}
+}
@@ -0,0 +1,362 @@
+pub mod native;
+
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::Error;
+use maingate::{
+    AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RangeChip, RangeConfig,
+    RangeInstructions, RegionCtx, Term, UnassignedValue,
+};
+use native::FixedPoint;
+
+#[derive(Clone, Debug)]
+pub struct FixedPointConfig {
+    main_gate_config: MainGateConfig,
+    range_config: RangeConfig,
+    bit_len_lookup: usize,
+}
+
+impl FixedPointConfig {
+    pub fn new(
+        main_gate_config: MainGateConfig,
+        range_config: RangeConfig,
+        bit_len_lookup: usize,
+    ) -> Self {
+        Self {
+            main_gate_config,
+            range_config,
+            bit_len_lookup,
+        }
+    }
+}
+
+/// Number of bits needed for the remainders of a division by `divisor`.
+fn remainder_bit_len(divisor: u64) -> usize {
+    assert!(divisor > 1);
+    (u64::BITS - (divisor - 1).leading_zeros()) as usize
+}
+
+/// Raw value of a fixed-point number with scale `SCALE`.
+#[derive(Clone, Debug)]
+pub struct AssignedFixedPoint<F: FieldExt, const SCALE: u64>(AssignedValue<F>);
+
+impl<F: FieldExt, const SCALE: u64> AssignedFixedPoint<F, SCALE> {
+    pub fn raw(&self) -> &AssignedValue<F> {
+        &self.0
+    }
+}
+
+/// Non-negative fixed-point arithmetic with scale `SCALE`. Every value the chip
+/// produces is range checked to `BITS` bits, so products never wrap around the
+/// field.
+pub struct FixedPointChip<F: FieldExt, const SCALE: u64, const BITS: usize> {
+    main_gate: MainGate<F>,
+    range_chip: RangeChip<F>,
+    bit_len_lookup: usize,
+}
+
+impl<F: FieldExt, const SCALE: u64, const BITS: usize> FixedPointChip<F, SCALE, BITS> {
+    pub fn new(config: FixedPointConfig) -> Self {
+        assert!(SCALE > 1);
+        assert!(BITS <= 64 && 2 * BITS < F::NUM_BITS as usize);
+        Self {
+            main_gate: MainGate::new(config.main_gate_config),
+            range_chip: RangeChip::new(config.range_config, config.bit_len_lookup),
+            bit_len_lookup: config.bit_len_lookup,
+        }
+    }
+
+    /// Overflow bit lengths `RangeChip::configure` needs for the chip to range
+    /// check its values and the remainders of divisions by `SCALE` and `divisors`.
+    pub fn overflow_lengths(bit_len_lookup: usize, divisors: &[u64]) -> Vec<usize> {
+        let mut lengths = vec![BITS, remainder_bit_len(SCALE)];
+        lengths.extend(divisors.iter().map(|d| remainder_bit_len(*d)));
+
+        let mut overflow_lengths: Vec<usize> = lengths
+            .iter()
+            .map(|len| len % bit_len_lookup)
+            .filter(|len| *len != 0)
+            .collect();
+        overflow_lengths.sort();
+        overflow_lengths.dedup();
+        overflow_lengths
+    }
+
+    fn range_check(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        value: &AssignedValue<F>,
+        bit_len: usize,
+    ) -> Result<(), Error> {
+        let unassigned = UnassignedValue::from(value.value().cloned());
+        let ranged = self
+            .range_chip
+            .range_value(ctx, &unassigned, self.bit_len_lookup, bit_len)?;
+        self.main_gate.assert_equal(ctx, value, &ranged)
+    }
+
+    /// Returns `value / divisor` rounded towards zero, proving
+    /// `value = quotient * divisor + remainder` with `remainder < divisor`.
+    fn div_rem(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        value: &AssignedValue<F>,
+        divisor: u64,
+    ) -> Result<AssignedValue<F>, Error> {
+        let quotient = value
+            .value()
+            .map(|v| F::from_u128(v.get_lower_128() / divisor as u128));
+        let remainder = value
+            .value()
+            .map(|v| F::from_u128(v.get_lower_128() % divisor as u128));
+
+        let quotient = self.range_chip.range_value(
+            ctx,
+            &UnassignedValue::from(quotient),
+            self.bit_len_lookup,
+            BITS,
+        )?;
+
+        let remainder_bit_len = remainder_bit_len(divisor);
+        let complement = remainder.map(|r| F::from(divisor - 1) - r);
+        let remainder = self.range_chip.range_value(
+            ctx,
+            &UnassignedValue::from(remainder),
+            self.bit_len_lookup,
+            remainder_bit_len,
+        )?;
+
+        // The range check only bounds the remainder by a power of two, so
+        // `divisor - 1 - remainder` has to fit the same bits as well.
+        if !divisor.is_power_of_two() {
+            let complement = self.range_chip.range_value(
+                ctx,
+                &UnassignedValue::from(complement),
+                self.bit_len_lookup,
+                remainder_bit_len,
+            )?;
+            let sum = self.main_gate.compose(
+                ctx,
+                &[
+                    Term::Assigned(&remainder, F::one()),
+                    Term::Assigned(&complement, F::one()),
+                ],
+                -F::from(divisor - 1),
+            )?;
+            self.main_gate.assert_zero(ctx, &sum)?;
+        }
+
+        let composed = self.main_gate.compose(
+            ctx,
+            &[
+                Term::Assigned(&quotient, F::from(divisor)),
+                Term::Assigned(&remainder, F::one()),
+            ],
+            F::zero(),
+        )?;
+        self.main_gate.assert_equal(ctx, &composed, value)?;
+
+        Ok(quotient)
+    }
+
+    pub fn assign(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        value: Option<FixedPoint<SCALE>>,
+    ) -> Result<AssignedFixedPoint<F, SCALE>, Error> {
+        let unassigned = UnassignedValue::from(value.map(|v| v.to_field()));
+        let assigned = self
+            .range_chip
+            .range_value(ctx, &unassigned, self.bit_len_lookup, BITS)?;
+        Ok(AssignedFixedPoint(assigned))
+    }
+
+    /// Range checks an already assigned raw value.
+    pub fn range_checked(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        value: &AssignedValue<F>,
+    ) -> Result<AssignedFixedPoint<F, SCALE>, Error> {
+        self.range_check(ctx, value, BITS)?;
+        Ok(AssignedFixedPoint(value.clone()))
+    }
+
+    pub fn add(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        a: &AssignedFixedPoint<F, SCALE>,
+        b: &AssignedFixedPoint<F, SCALE>,
+    ) -> Result<AssignedFixedPoint<F, SCALE>, Error> {
+        let sum = self.main_gate.add(ctx, &a.0, &b.0)?;
+        self.range_check(ctx, &sum, BITS)?;
+        Ok(AssignedFixedPoint(sum))
+    }
+
+    /// Multiplies and rescales the product back to `SCALE`.
+    pub fn mul(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        a: &AssignedFixedPoint<F, SCALE>,
+        b: &AssignedFixedPoint<F, SCALE>,
+    ) -> Result<AssignedFixedPoint<F, SCALE>, Error> {
+        let product = self.main_gate.mul(ctx, &a.0, &b.0)?;
+        let rescaled = self.div_rem(ctx, &product, SCALE)?;
+        Ok(AssignedFixedPoint(rescaled))
+    }
+
+    pub fn div_constant(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        a: &AssignedFixedPoint<F, SCALE>,
+        divisor: u64,
+    ) -> Result<AssignedFixedPoint<F, SCALE>, Error> {
+        if divisor == 1 {
+            return Ok(a.clone());
+        }
+        let quotient = self.div_rem(ctx, &a.0, divisor)?;
+        Ok(AssignedFixedPoint(quotient))
+    }
+
+    /// Drops the fractional part.
+    pub fn truncate(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        a: &AssignedFixedPoint<F, SCALE>,
+    ) -> Result<AssignedFixedPoint<F, SCALE>, Error> {
+        let integer = self.div_rem(ctx, &a.0, SCALE)?;
+        let truncated =
+            self.main_gate
+                .compose(ctx, &[Term::Assigned(&integer, F::from(SCALE))], F::zero())?;
+        Ok(AssignedFixedPoint(truncated))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Column, ConstraintSystem, Instance},
+    };
+
+    const SCALE: u64 = 1000;
+    const BITS: usize = 32;
+    const BIT_LEN_LOOKUP: usize = 8;
+    const DIVISOR: u64 = 3;
+
+    type Fixed = FixedPoint<SCALE>;
+    type TestFixedPointChip = FixedPointChip<Fr, SCALE, BITS>;
+
+    #[derive(Clone)]
+    struct FixedPointTesterConfig {
+        fixed_point: FixedPointConfig,
+        results: Column<Instance>,
+    }
+
+    struct FixedPointTester {
+        a: Option<Fixed>,
+        b: Option<Fixed>,
+    }
+
+    impl Circuit<Fr> for FixedPointTester {
+        type Config = FixedPointTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self { a: None, b: None }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let main_gate_config = MainGate::<Fr>::configure(meta);
+            let overflow_bit_lengths =
+                TestFixedPointChip::overflow_lengths(BIT_LEN_LOOKUP, &[DIVISOR]);
+            let range_config =
+                RangeChip::<Fr>::configure(meta, &main_gate_config, overflow_bit_lengths);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                fixed_point: FixedPointConfig::new(main_gate_config, range_config, BIT_LEN_LOOKUP),
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let chip = TestFixedPointChip::new(config.fixed_point.clone());
+
+            let results = layouter.assign_region(
+                || "fixed_point",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+
+                    let a = chip.assign(ctx, self.a)?;
+                    let b = chip.assign(ctx, self.b)?;
+
+                    let sum = chip.add(ctx, &a, &b)?;
+                    let product = chip.mul(ctx, &a, &b)?;
+                    let quotient = chip.div_constant(ctx, &b, DIVISOR)?;
+                    let truncated = chip.truncate(ctx, &b)?;
+
+                    Ok([sum, product, quotient, truncated])
+                },
+            )?;
+
+            let range_chip =
+                RangeChip::<Fr>::new(config.fixed_point.range_config.clone(), BIT_LEN_LOOKUP);
+            range_chip.load_limb_range_table(&mut layouter)?;
+            range_chip.load_overflow_range_tables(&mut layouter)?;
+
+            for (i, result) in results.iter().enumerate() {
+                layouter.constrain_instance(result.raw().cell(), config.results, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_match_native_fixed_point() {
+        let a = Fixed::from_raw(1_500);
+        let b = Fixed::from_raw(2_333);
+
+        let expected: Vec<Fr> = [a + b, a * b, b.div_constant(DIVISOR), b.truncate()]
+            .iter()
+            .map(|x| x.to_field())
+            .collect();
+
+        let tester = FixedPointTester {
+            a: Some(a),
+            b: Some(b),
+        };
+
+        let k = 10;
+        let prover = MockProver::run(k, &tester, vec![expected]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_fail_on_overflowing_value() {
+        let a = Fixed::from_raw(1 << BITS);
+        let b = Fixed::from_raw(1);
+
+        let expected: Vec<Fr> = [a + b, a * b, b.div_constant(DIVISOR), b.truncate()]
+            .iter()
+            .map(|x| x.to_field())
+            .collect();
+
+        let tester = FixedPointTester {
+            a: Some(a),
+            b: Some(b),
+        };
+
+        let k = 10;
+        let prover = MockProver::run(k, &tester, vec![expected]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use halo2_proofs::arithmetic::FieldExt;
+use std::ops::{Add, Mul};
+
+/// Non-negative fixed-point number `raw / SCALE`. Every operation rounds
+/// towards zero, the same way `FixedPointChip` does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FixedPoint<const SCALE: u64>(u64);
+
+impl<const SCALE: u64> FixedPoint<SCALE> {
+    pub fn from_raw(raw: u64) -> Self {
+        Self(raw)
+    }
+
+    pub fn from_integer(n: u64) -> Self {
+        Self(n * SCALE)
+    }
+
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+
+    pub fn div_constant(self, divisor: u64) -> Self {
+        Self(self.0 / divisor)
+    }
+
+    /// Drops the fractional part.
+    pub fn truncate(self) -> Self {
+        Self(self.0 / SCALE * SCALE)
+    }
+
+    pub fn to_field<F: FieldExt>(&self) -> F {
+        F::from(self.0)
+    }
+}
+
+impl<const SCALE: u64> Add for FixedPoint<SCALE> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl<const SCALE: u64> Mul for FixedPoint<SCALE> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        let product = self.0 as u128 * rhs.0 as u128;
+        Self((product / SCALE as u128) as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type Fixed = FixedPoint<1000>;
+
+    #[test]
+    fn should_round_towards_zero() {
+        let a = Fixed::from_raw(1_500);
+        let b = Fixed::from_raw(2_333);
+
+        assert_eq!(a + b, Fixed::from_raw(3_833));
+        assert_eq!(a * b, Fixed::from_raw(3_499));
+        assert_eq!(b.div_constant(3), Fixed::from_raw(777));
+        assert_eq!(b.truncate(), Fixed::from_integer(2));
+    }
+}
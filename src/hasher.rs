@@ -0,0 +1,116 @@
+use crate::poseidon::{
+    self,
+    native::Poseidon,
+    params::{PolySbox, RoundParams},
+    wrong::PoseidonChip,
+    PoseidonConfig,
+};
+use crate::rescue::{
+    self, native::RescuePrime, params::RescueParams, wrong::RescueChip, RescueConfig,
+};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::circuit::{AssignedCell, Layouter};
+use halo2_proofs::plonk::{ConstraintSystem, Error};
+use maingate::{AssignedValue, MainGateConfig, RegionCtx};
+use std::fmt::Debug;
+
+/// Permutation the sponge, the Merkle trees and EigenTrust hash with, natively
+/// and with `MainGate` operations. Implemented by `Poseidon` and `RescuePrime`,
+/// so the code built on it can switch algebraic hashes.
+pub trait Hasher<F: FieldExt, const WIDTH: usize> {
+    fn permute(inputs: [F; WIDTH]) -> [F; WIDTH];
+
+    fn permute_assigned(
+        main_gate_config: &MainGateConfig,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        inputs: [AssignedValue<F>; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error>;
+}
+
+/// `Hasher` that also has a custom-gate chip, for the circuits built on those
+/// chips rather than on `MainGate`. Only the polynomial S-boxes have one.
+pub trait GateHasher<F: FieldExt, const WIDTH: usize>: Hasher<F, WIDTH> {
+    type Config: Clone + Debug;
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config;
+
+    /// Permutes `inputs` in the regions of the chip and returns the output
+    /// cells.
+    fn permute_cells(
+        config: &Self::Config,
+        layouter: impl Layouter<F>,
+        inputs: [AssignedCell<F, F>; WIDTH],
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error>;
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> Hasher<F, WIDTH> for Poseidon<F, WIDTH, P>
+where
+    P: RoundParams<F, WIDTH>,
+{
+    fn permute(inputs: [F; WIDTH]) -> [F; WIDTH] {
+        Poseidon::<F, WIDTH, P>::new(inputs).permute()
+    }
+
+    fn permute_assigned(
+        main_gate_config: &MainGateConfig,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        inputs: [AssignedValue<F>; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        PoseidonChip::<F, WIDTH, P>::new(main_gate_config.clone()).permute(ctx, inputs)
+    }
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> Hasher<F, WIDTH> for RescuePrime<F, WIDTH, P>
+where
+    P: RescueParams<F, WIDTH>,
+{
+    fn permute(inputs: [F; WIDTH]) -> [F; WIDTH] {
+        RescuePrime::<F, WIDTH, P>::new(inputs).permute()
+    }
+
+    fn permute_assigned(
+        main_gate_config: &MainGateConfig,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        inputs: [AssignedValue<F>; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        RescueChip::<F, WIDTH, P>::new(main_gate_config.clone()).permute(ctx, inputs)
+    }
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> GateHasher<F, WIDTH> for Poseidon<F, WIDTH, P>
+where
+    P: RoundParams<F, WIDTH> + PolySbox,
+{
+    type Config = PoseidonConfig<WIDTH>;
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        poseidon::PoseidonChip::<F, WIDTH, P>::configure(meta)
+    }
+
+    fn permute_cells(
+        config: &Self::Config,
+        layouter: impl Layouter<F>,
+        inputs: [AssignedCell<F, F>; WIDTH],
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        poseidon::PoseidonChip::<F, WIDTH, P>::new(inputs).permute(config, layouter)
+    }
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> GateHasher<F, WIDTH> for RescuePrime<F, WIDTH, P>
+where
+    P: RescueParams<F, WIDTH> + PolySbox,
+{
+    type Config = RescueConfig<WIDTH>;
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        rescue::RescueChip::<F, WIDTH, P>::configure(meta)
+    }
+
+    fn permute_cells(
+        config: &Self::Config,
+        layouter: impl Layouter<F>,
+        inputs: [AssignedCell<F, F>; WIDTH],
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        rescue::RescueChip::<F, WIDTH, P>::new(inputs).permute(config, layouter)
+    }
+}
@@ -3,6 +3,12 @@
 
 mod accumulator;
 mod ecdsa;
+mod eigen;
+mod fixed_point;
+mod hasher;
+mod merkle;
 mod poseidon;
+mod poseidon2;
+mod rescue;
 
 fn main() {}
@@ -0,0 +1,308 @@
+pub mod native;
+pub mod wrong;
+
+use crate::hasher::GateHasher;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Region},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use native::MerkleProof;
+use std::marker::PhantomData;
+
+#[derive(Clone, Debug)]
+pub struct MerkleConfig<C> {
+    hasher_config: C,
+    node: Column<Advice>,
+    sibling: Column<Advice>,
+    bit: Column<Advice>,
+    left: Column<Advice>,
+    right: Column<Advice>,
+    path_selector: Selector,
+}
+
+/// Inclusion proof check with a custom gate: every level orders the current
+/// node and its sibling by a boolean path bit and hashes them with the
+/// custom-gate chip of `H`, the same way `MerkleProof::root` does natively.
+pub struct MerkleChip<F: FieldExt, const DEPTH: usize, H>
+where
+    H: GateHasher<F, 5>,
+{
+    _hasher: PhantomData<(F, H)>,
+}
+
+impl<F: FieldExt, const DEPTH: usize, H> MerkleChip<F, DEPTH, H>
+where
+    H: GateHasher<F, 5>,
+{
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> MerkleConfig<H::Config> {
+        let hasher_config = H::configure(meta);
+        Self::configure_with_hasher(meta, hasher_config)
+    }
+
+    /// Configures the path gate around a hasher chip configured by the caller,
+    /// e.g. a Poseidon chip with its own `PoseidonLayout`.
+    pub fn configure_with_hasher(
+        meta: &mut ConstraintSystem<F>,
+        hasher_config: H::Config,
+    ) -> MerkleConfig<H::Config> {
+        let [node, sibling, bit, left, right] = [(); 5].map(|_| {
+            let column = meta.advice_column();
+            meta.enable_equality(column);
+            column
+        });
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+        let path_selector = meta.selector();
+
+        meta.create_gate("merkle_path", |v_cells| {
+            let s = v_cells.query_selector(path_selector);
+            let node = v_cells.query_advice(node, Rotation::cur());
+            let sibling = v_cells.query_advice(sibling, Rotation::cur());
+            let bit = v_cells.query_advice(bit, Rotation::cur());
+            let left = v_cells.query_advice(left, Rotation::cur());
+            let right = v_cells.query_advice(right, Rotation::cur());
+            let one = Expression::Constant(F::one());
+
+            // When the bit is set the current node is the right child.
+            let is_bool = bit.clone() * (one - bit.clone());
+            let left_exp = node.clone() + bit.clone() * (sibling.clone() - node.clone());
+            let right_exp = sibling.clone() + bit * (node - sibling);
+
+            vec![
+                s.clone() * is_bool,
+                s.clone() * (left - left_exp),
+                s * (right - right_exp),
+            ]
+        });
+
+        MerkleConfig {
+            hasher_config,
+            node,
+            sibling,
+            bit,
+            left,
+            right,
+            path_selector,
+        }
+    }
+
+    /// Orders `node` and the sibling of one level, and assigns the zero lanes
+    /// of the hash input.
+    fn assign_level(
+        config: &MerkleConfig<H::Config>,
+        region: &mut Region<'_, F>,
+        node: &AssignedCell<F, F>,
+        sibling: Option<F>,
+        bit: Option<bool>,
+    ) -> Result<[AssignedCell<F, F>; 5], Error> {
+        let round = 0;
+        config.path_selector.enable(region, round)?;
+
+        let node = node.copy_advice(|| "node", region, config.node, round)?;
+        region.assign_advice(
+            || "sibling",
+            config.sibling,
+            round,
+            || sibling.ok_or(Error::Synthesis),
+        )?;
+        let bit = bit.map(|b| if b { F::one() } else { F::zero() });
+        region.assign_advice(|| "bit", config.bit, round, || bit.ok_or(Error::Synthesis))?;
+
+        let (left, right) = match (node.value(), sibling, bit) {
+            (Some(&n), Some(s), Some(b)) => (Some(n + b * (s - n)), Some(s + b * (n - s))),
+            _ => (None, None),
+        };
+        let left = region.assign_advice(
+            || "left",
+            config.left,
+            round,
+            || left.ok_or(Error::Synthesis),
+        )?;
+        let right = region.assign_advice(
+            || "right",
+            config.right,
+            round,
+            || right.ok_or(Error::Synthesis),
+        )?;
+
+        let [zero_1, zero_2, zero_3] = [config.node, config.sibling, config.bit].map(|column| {
+            region.assign_advice_from_constant(|| "zero", column, round + 1, F::zero())
+        });
+        Ok([left, right, zero_1?, zero_2?, zero_3?])
+    }
+
+    /// Root `proof` leads to from `leaf`.
+    pub fn root(
+        config: &MerkleConfig<H::Config>,
+        mut layouter: impl Layouter<F>,
+        leaf: &AssignedCell<F, F>,
+        proof: Option<MerkleProof<F, DEPTH>>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let mut node = leaf.clone();
+        for level in 0..DEPTH {
+            let sibling = proof.map(|p| p.siblings[level]);
+            let bit = proof.map(|p| p.path[level]);
+            let inputs = layouter.assign_region(
+                || format!("level_{}", level),
+                |mut region: Region<'_, F>| {
+                    Self::assign_level(config, &mut region, &node, sibling, bit)
+                },
+            )?;
+
+            let out = H::permute_cells(
+                &config.hasher_config,
+                layouter.namespace(|| format!("hash_{}", level)),
+                inputs,
+            )?;
+            node = out[0].clone();
+        }
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::{native::Poseidon, params::bn254_5x5::Params5x5Bn254};
+    use crate::rescue::{native::RescuePrime, params as rescue_params};
+    use halo2_proofs::{
+        arithmetic::Field,
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Instance},
+    };
+    use native::MerkleTree;
+
+    const DEPTH: usize = 3;
+
+    type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
+
+    type TestRescuePrime = RescuePrime<Fr, 5, rescue_params::Params5x5Bn254>;
+
+    type TestMerkleTree = MerkleTree<Fr, DEPTH, TestPoseidon>;
+
+    #[derive(Clone)]
+    struct MerkleTesterConfig<C> {
+        merkle_config: MerkleConfig<C>,
+        results: Column<Instance>,
+    }
+
+    struct MerkleTester<H: GateHasher<Fr, 5>> {
+        leaf: Option<Fr>,
+        proof: Option<MerkleProof<Fr, DEPTH>>,
+        _hasher: PhantomData<H>,
+    }
+
+    impl<H: GateHasher<Fr, 5>> MerkleTester<H> {
+        fn new(leaf: Fr, proof: MerkleProof<Fr, DEPTH>) -> Self {
+            Self {
+                leaf: Some(leaf),
+                proof: Some(proof),
+                _hasher: PhantomData,
+            }
+        }
+    }
+
+    impl<H: GateHasher<Fr, 5>> Circuit<Fr> for MerkleTester<H> {
+        type Config = MerkleTesterConfig<H::Config>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                leaf: None,
+                proof: None,
+                _hasher: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let merkle_config = MerkleChip::<Fr, DEPTH, H>::configure(meta);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                merkle_config,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let leaf = layouter.assign_region(
+                || "load_leaf",
+                |mut region: Region<'_, Fr>| {
+                    region.assign_advice(
+                        || "leaf",
+                        config.merkle_config.node,
+                        0,
+                        || self.leaf.ok_or(Error::Synthesis),
+                    )
+                },
+            )?;
+
+            let root = MerkleChip::<Fr, DEPTH, H>::root(
+                &config.merkle_config,
+                layouter.namespace(|| "merkle"),
+                &leaf,
+                self.proof,
+            )?;
+
+            layouter.constrain_instance(root.cell(), config.results, 0)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_verify_inclusion_path() {
+        let leaves: Vec<Fr> = (1..8u64).map(|n| Fr::from(n)).collect();
+        let tree = TestMerkleTree::new(&leaves);
+
+        let proof = tree.proof(6);
+        assert!(proof.verify::<TestPoseidon>(leaves[6], tree.root()));
+
+        let merkle_tester = MerkleTester::<TestPoseidon>::new(leaves[6], proof);
+
+        let k = 10;
+        let prover = MockProver::run(k, &merkle_tester, vec![vec![tree.root()]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_fail_on_wrong_sibling() {
+        let leaves: Vec<Fr> = (1..8u64).map(|n| Fr::from(n)).collect();
+        let tree = TestMerkleTree::new(&leaves);
+
+        let mut proof = tree.proof(2);
+        proof.siblings[1] += Fr::one();
+        assert!(!proof.verify::<TestPoseidon>(leaves[2], tree.root()));
+
+        let merkle_tester = MerkleTester::<TestPoseidon>::new(leaves[2], proof);
+
+        let k = 10;
+        let prover = MockProver::run(k, &merkle_tester, vec![vec![tree.root()]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn should_verify_inclusion_path_with_rescue_prime() {
+        let leaves: Vec<Fr> = (1..8u64).map(|n| Fr::from(n)).collect();
+        let tree = MerkleTree::<Fr, DEPTH, TestRescuePrime>::new(&leaves);
+
+        let merkle_tester = MerkleTester::<TestRescuePrime>::new(leaves[6], tree.proof(6));
+
+        let k = 10;
+        let prover = MockProver::run(k, &merkle_tester, vec![vec![tree.root()]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let poseidon_root = TestMerkleTree::new(&leaves).root();
+        let prover = MockProver::run(k, &merkle_tester, vec![vec![poseidon_root]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,128 @@
+pub mod sparse;
+
+use crate::hasher::Hasher;
+use halo2_proofs::arithmetic::FieldExt;
+use std::marker::PhantomData;
+
+/// Two-to-one hash of the tree: `H([left, right, 0, 0, 0])[0]`.
+pub fn hash<F: FieldExt, H: Hasher<F, 5>>(left: F, right: F) -> F {
+    let inputs = [left, right, F::zero(), F::zero(), F::zero()];
+    H::permute(inputs)[0]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MerkleProof<F: FieldExt, const DEPTH: usize> {
+    /// Siblings of the nodes on the path, from the leaf level up.
+    pub siblings: [F; DEPTH],
+    /// `true` where the node on the path is a right child.
+    pub path: [bool; DEPTH],
+}
+
+impl<F: FieldExt, const DEPTH: usize> MerkleProof<F, DEPTH> {
+    /// Root of the tree `leaf` belongs to according to this proof.
+    pub fn root<H: Hasher<F, 5>>(&self, leaf: F) -> F {
+        let mut node = leaf;
+        for i in 0..DEPTH {
+            node = if self.path[i] {
+                hash::<F, H>(self.siblings[i], node)
+            } else {
+                hash::<F, H>(node, self.siblings[i])
+            };
+        }
+        node
+    }
+
+    pub fn verify<H: Hasher<F, 5>>(&self, leaf: F, root: F) -> bool {
+        self.root::<H>(leaf) == root
+    }
+}
+
+/// Full binary tree with `2^DEPTH` leaves, unused leaves are zero.
+pub struct MerkleTree<F: FieldExt, const DEPTH: usize, H>
+where
+    H: Hasher<F, 5>,
+{
+    /// Nodes of every level, from the leaves up to the root.
+    nodes: Vec<Vec<F>>,
+    _hasher: PhantomData<H>,
+}
+
+impl<F: FieldExt, const DEPTH: usize, H> MerkleTree<F, DEPTH, H>
+where
+    H: Hasher<F, 5>,
+{
+    pub fn new(leaves: &[F]) -> Self {
+        assert!(leaves.len() <= 1 << DEPTH);
+
+        let mut level = vec![F::zero(); 1 << DEPTH];
+        level[..leaves.len()].copy_from_slice(leaves);
+
+        let mut nodes = vec![level];
+        for i in 0..DEPTH {
+            let next = nodes[i]
+                .chunks(2)
+                .map(|pair| hash::<F, H>(pair[0], pair[1]))
+                .collect();
+            nodes.push(next);
+        }
+
+        Self {
+            nodes,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn root(&self) -> F {
+        self.nodes[DEPTH][0]
+    }
+
+    pub fn proof(&self, index: usize) -> MerkleProof<F, DEPTH> {
+        assert!(index < 1 << DEPTH);
+
+        let mut siblings = [F::zero(); DEPTH];
+        let mut path = [false; DEPTH];
+        let mut index = index;
+        for level in 0..DEPTH {
+            path[level] = index & 1 == 1;
+            siblings[level] = self.nodes[level][index ^ 1];
+            index >>= 1;
+        }
+
+        MerkleProof { siblings, path }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::{native::Poseidon, params::bn254_5x5::Params5x5Bn254};
+    use halo2_proofs::pairing::bn256::Fr;
+
+    type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
+
+    type TestMerkleTree = MerkleTree<Fr, 3, TestPoseidon>;
+
+    #[test]
+    fn should_build_root_from_proof() {
+        let leaves: Vec<Fr> = (1..6u64).map(|n| Fr::from(n)).collect();
+        let tree = TestMerkleTree::new(&leaves);
+
+        for (i, leaf) in leaves.iter().enumerate() {
+            let proof = tree.proof(i);
+            assert_eq!(proof.root::<TestPoseidon>(*leaf), tree.root());
+        }
+
+        let proof = tree.proof(0);
+        assert_ne!(proof.root::<TestPoseidon>(Fr::from(7)), tree.root());
+    }
+
+    #[test]
+    fn should_hash_levels() {
+        let leaves: Vec<Fr> = (1..5u64).map(|n| Fr::from(n)).collect();
+        let tree = MerkleTree::<Fr, 2, TestPoseidon>::new(&leaves);
+
+        let left = hash::<Fr, TestPoseidon>(leaves[0], leaves[1]);
+        let right = hash::<Fr, TestPoseidon>(leaves[2], leaves[3]);
+        assert_eq!(tree.root(), hash::<Fr, TestPoseidon>(left, right));
+    }
+}
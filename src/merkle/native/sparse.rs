@@ -0,0 +1,365 @@
+use super::{hash, MerkleProof};
+use crate::hasher::Hasher;
+use halo2_proofs::arithmetic::FieldExt;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SparseMerkleError {
+    /// The key has a bit set at or above `DEPTH`, so it has no leaf.
+    KeyOutOfRange,
+}
+
+/// Leaf of `key` holding `value`: `H([key, value, 1, 0, 0])[0]`. The
+/// third lane keeps leaves apart from inner nodes, empty leaves are zero.
+pub fn leaf_hash<F: FieldExt, H: Hasher<F, 5>>(key: F, value: F) -> F {
+    let inputs = [key, value, F::one(), F::zero(), F::zero()];
+    H::permute(inputs)[0]
+}
+
+/// The low `DEPTH` bits of `key`, least significant first. They are the path
+/// of its leaf, bit `i` is set when the node at level `i` is a right child.
+pub fn key_path<F: FieldExt, const DEPTH: usize>(key: &F) -> [bool; DEPTH] {
+    let repr = key.to_repr();
+    let bytes = repr.as_ref();
+    let mut path = [false; DEPTH];
+    for i in 0..DEPTH {
+        path[i] = (bytes[i / 8] >> (i % 8)) & 1 == 1;
+    }
+    path
+}
+
+/// Whether `key` is below `2^DEPTH`, so that `key_path` is all of it.
+fn in_range<F: FieldExt, const DEPTH: usize>(key: &F) -> bool {
+    let repr = key.to_repr();
+    let bytes = repr.as_ref();
+    (DEPTH..bytes.len() * 8).all(|i| (bytes[i / 8] >> (i % 8)) & 1 == 0)
+}
+
+/// Position of the node at `level` above `key`: the representation of the key
+/// with the bits below `level` cleared.
+fn node_index<F: FieldExt>(key: &F, level: usize) -> Vec<u8> {
+    let mut index = key.to_repr().as_ref().to_vec();
+    for i in 0..level {
+        index[i / 8] &= !(1 << (i % 8));
+    }
+    index
+}
+
+/// Sparse Merkle tree of depth `DEPTH` over keys below `2^DEPTH`, every key has
+/// its own leaf at the position given by its bits. With `DEPTH` set to the bit
+/// size of `F` every field element is a key, e.g. a nullifier.
+///
+/// Only the non-empty nodes are stored, and `insert` and `remove` rehash the
+/// path of their key alone.
+pub struct SparseMerkleTree<F: FieldExt, const DEPTH: usize, H>
+where
+    H: Hasher<F, 5>,
+{
+    /// Key and value of every non-empty leaf, by the representation of the key.
+    leaves: BTreeMap<Vec<u8>, (F, F)>,
+    /// Non-empty nodes by level and `node_index`, leaves at level `0`.
+    nodes: BTreeMap<(usize, Vec<u8>), F>,
+    /// Root of an empty subtree of every height.
+    empty: Vec<F>,
+    root: F,
+    _hasher: PhantomData<H>,
+}
+
+impl<F: FieldExt, const DEPTH: usize, H> SparseMerkleTree<F, DEPTH, H>
+where
+    H: Hasher<F, 5>,
+{
+    pub fn new() -> Self {
+        assert!(DEPTH <= F::NUM_BITS as usize);
+
+        let mut empty = vec![F::zero()];
+        for h in 0..DEPTH {
+            empty.push(hash::<F, H>(empty[h], empty[h]));
+        }
+
+        Self {
+            leaves: BTreeMap::new(),
+            nodes: BTreeMap::new(),
+            root: empty[DEPTH],
+            empty,
+            _hasher: PhantomData,
+        }
+    }
+
+    fn check_key(key: &F) -> Result<(), SparseMerkleError> {
+        if !in_range::<F, DEPTH>(key) {
+            return Err(SparseMerkleError::KeyOutOfRange);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &F) -> Option<F> {
+        self.leaves
+            .get(key.to_repr().as_ref())
+            .map(|(_, value)| *value)
+    }
+
+    /// Sets the value of `key` and returns the new root.
+    pub fn insert(&mut self, key: F, value: F) -> Result<F, SparseMerkleError> {
+        Self::check_key(&key)?;
+        self.leaves
+            .insert(key.to_repr().as_ref().to_vec(), (key, value));
+        self.update_path(&key, leaf_hash::<F, H>(key, value));
+        Ok(self.root)
+    }
+
+    /// Empties the leaf of `key` and returns the new root.
+    pub fn remove(&mut self, key: &F) -> Result<F, SparseMerkleError> {
+        Self::check_key(key)?;
+        self.leaves.remove(key.to_repr().as_ref());
+        self.update_path(key, F::zero());
+        Ok(self.root)
+    }
+
+    fn node(&self, level: usize, index: &[u8]) -> F {
+        let node = self.nodes.get(&(level, index.to_vec()));
+        node.copied().unwrap_or(self.empty[level])
+    }
+
+    /// Index of the sibling of the node at `level` above `key`.
+    fn sibling_index(key: &F, level: usize) -> Vec<u8> {
+        let mut index = node_index(key, level);
+        index[level / 8] ^= 1 << (level % 8);
+        index
+    }
+
+    /// Sets the leaf of `key` to `leaf` and rehashes the nodes above it.
+    fn update_path(&mut self, key: &F, leaf: F) {
+        let path = key_path::<F, DEPTH>(key);
+        let mut node = leaf;
+        for level in 0..DEPTH {
+            let index = node_index(key, level);
+            if node == self.empty[level] {
+                self.nodes.remove(&(level, index));
+            } else {
+                self.nodes.insert((level, index), node);
+            }
+
+            let sibling = self.node(level, &Self::sibling_index(key, level));
+            node = if path[level] {
+                hash::<F, H>(sibling, node)
+            } else {
+                hash::<F, H>(node, sibling)
+            };
+        }
+        self.root = node;
+    }
+
+    pub fn root(&self) -> F {
+        self.root
+    }
+
+    /// Path of the leaf of `key`, whether or not the key is in the tree.
+    pub fn proof(&self, key: &F) -> Result<MerkleProof<F, DEPTH>, SparseMerkleError> {
+        Self::check_key(key)?;
+        let mut siblings = [F::zero(); DEPTH];
+        for (level, sibling) in siblings.iter_mut().enumerate() {
+            *sibling = self.node(level, &Self::sibling_index(key, level));
+        }
+        let path = key_path::<F, DEPTH>(key);
+
+        Ok(MerkleProof { siblings, path })
+    }
+}
+
+/// Checks `key` holds `value` in the tree of `root`.
+pub fn verify_membership<F: FieldExt, const DEPTH: usize, H: Hasher<F, 5>>(
+    root: F,
+    key: F,
+    value: F,
+    proof: &MerkleProof<F, DEPTH>,
+) -> bool {
+    let leaf = leaf_hash::<F, H>(key, value);
+    in_range::<F, DEPTH>(&key)
+        && proof.path == key_path::<F, DEPTH>(&key)
+        && proof.verify::<H>(leaf, root)
+}
+
+/// Checks the leaf of `key` is empty in the tree of `root`.
+pub fn verify_non_membership<F: FieldExt, const DEPTH: usize, H: Hasher<F, 5>>(
+    root: F,
+    key: F,
+    proof: &MerkleProof<F, DEPTH>,
+) -> bool {
+    in_range::<F, DEPTH>(&key)
+        && proof.path == key_path::<F, DEPTH>(&key)
+        && proof.verify::<H>(F::zero(), root)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eigen::native::nullifier;
+    use crate::poseidon::{native::Poseidon, params::bn254_5x5::Params5x5Bn254};
+    use ff::PrimeField;
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    const DEPTH: usize = 8;
+
+    type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
+
+    type TestSparseMerkleTree = SparseMerkleTree<Fr, DEPTH, TestPoseidon>;
+
+    /// Root of the tree over `leaves` rebuilt from scratch, level by level.
+    fn full_root(leaves: &[(Fr, Fr)]) -> Fr {
+        let mut level: Vec<Fr> = vec![Fr::zero(); 1 << DEPTH];
+        for (key, value) in leaves {
+            let index = key_path::<Fr, DEPTH>(key)
+                .iter()
+                .rev()
+                .fold(0, |acc, bit| acc * 2 + *bit as usize);
+            level[index] = leaf_hash::<_, TestPoseidon>(*key, *value);
+        }
+        for _ in 0..DEPTH {
+            level = level
+                .chunks(2)
+                .map(|pair| hash::<Fr, TestPoseidon>(pair[0], pair[1]))
+                .collect();
+        }
+        level[0]
+    }
+
+    #[test]
+    fn should_prove_membership_and_absence() {
+        let mut tree = TestSparseMerkleTree::new();
+        tree.insert(Fr::from(3), Fr::from(30)).unwrap();
+        tree.insert(Fr::from(200), Fr::from(2000)).unwrap();
+        let root = tree.insert(Fr::from(7), Fr::from(70)).unwrap();
+
+        let proof = tree.proof(&Fr::from(200)).unwrap();
+        assert!(verify_membership::<_, DEPTH, TestPoseidon>(
+            root,
+            Fr::from(200),
+            Fr::from(2000),
+            &proof
+        ));
+        assert!(!verify_membership::<_, DEPTH, TestPoseidon>(
+            root,
+            Fr::from(200),
+            Fr::from(2001),
+            &proof
+        ));
+        assert!(!verify_non_membership::<_, DEPTH, TestPoseidon>(
+            root,
+            Fr::from(200),
+            &proof
+        ));
+
+        let proof = tree.proof(&Fr::from(4)).unwrap();
+        assert!(verify_non_membership::<_, DEPTH, TestPoseidon>(
+            root,
+            Fr::from(4),
+            &proof
+        ));
+        // The path of another key does not prove anything about `4`.
+        let proof = tree.proof(&Fr::from(5)).unwrap();
+        assert!(!verify_non_membership::<_, DEPTH, TestPoseidon>(
+            root,
+            Fr::from(4),
+            &proof
+        ));
+    }
+
+    #[test]
+    fn should_update_with_same_siblings() {
+        let mut tree = TestSparseMerkleTree::new();
+        tree.insert(Fr::from(1), Fr::from(10)).unwrap();
+        let old_root = tree.insert(Fr::from(2), Fr::from(20)).unwrap();
+
+        let proof = tree.proof(&Fr::from(9)).unwrap();
+        let new_root = tree.insert(Fr::from(9), Fr::from(90)).unwrap();
+        assert_eq!(proof.root::<TestPoseidon>(Fr::zero()), old_root);
+        let leaf = leaf_hash::<_, TestPoseidon>(Fr::from(9), Fr::from(90));
+        assert_eq!(proof.root::<TestPoseidon>(leaf), new_root);
+
+        assert_eq!(tree.get(&Fr::from(9)), Some(Fr::from(90)));
+        assert_eq!(tree.remove(&Fr::from(9)), Ok(old_root));
+    }
+
+    #[test]
+    fn should_match_full_rebuild() {
+        let mut tree = TestSparseMerkleTree::new();
+        let mut leaves = Vec::new();
+        for n in [3u64, 200, 7, 8, 255, 0] {
+            leaves.push((Fr::from(n), Fr::from(n * 10 + 1)));
+            let root = tree.insert(Fr::from(n), Fr::from(n * 10 + 1)).unwrap();
+            assert_eq!(root, full_root(&leaves));
+        }
+
+        let root = tree.remove(&Fr::from(7)).unwrap();
+        leaves.retain(|(key, _)| *key != Fr::from(7));
+        assert_eq!(root, full_root(&leaves));
+    }
+
+    #[test]
+    fn should_start_from_empty_root() {
+        let tree = TestSparseMerkleTree::new();
+        let proof = tree.proof(&Fr::from(42)).unwrap();
+        assert!(verify_non_membership::<_, DEPTH, TestPoseidon>(
+            tree.root(),
+            Fr::from(42),
+            &proof
+        ));
+    }
+
+    #[test]
+    fn should_reject_out_of_range_keys() {
+        // Shares its path with `0`, only the low `DEPTH` bits pick the leaf.
+        let mut tree = TestSparseMerkleTree::new();
+        let root = tree.insert(Fr::zero(), Fr::from(10)).unwrap();
+        let key = Fr::from(1 << DEPTH);
+        assert_eq!(tree.remove(&key), Err(SparseMerkleError::KeyOutOfRange));
+        assert_eq!(
+            tree.insert(key, Fr::one()),
+            Err(SparseMerkleError::KeyOutOfRange)
+        );
+        assert_eq!(tree.proof(&key), Err(SparseMerkleError::KeyOutOfRange));
+        assert_eq!(tree.root(), root);
+
+        // Nor does the proof of `0` speak about it.
+        let proof = tree.proof(&Fr::zero()).unwrap();
+        assert!(!verify_membership::<_, DEPTH, TestPoseidon>(
+            root,
+            key,
+            Fr::from(10),
+            &proof
+        ));
+    }
+
+    #[test]
+    fn should_hold_nullifiers_at_full_depth() {
+        const FULL_DEPTH: usize = Fr::NUM_BITS as usize;
+        type NullifierTree = SparseMerkleTree<Fr, FULL_DEPTH, TestPoseidon>;
+
+        let mut tree = NullifierTree::new();
+        let nullifiers =
+            [1u64, 2, 3].map(|secret| nullifier::<_, TestPoseidon>(Fr::from(secret), Fr::from(7)));
+        for n in &nullifiers[..2] {
+            tree.insert(*n, Fr::one()).unwrap();
+        }
+        let root = tree.root();
+
+        let proof = tree.proof(&nullifiers[0]).unwrap();
+        assert!(verify_membership::<_, FULL_DEPTH, TestPoseidon>(
+            root,
+            nullifiers[0],
+            Fr::one(),
+            &proof
+        ));
+        let proof = tree.proof(&nullifiers[2]).unwrap();
+        assert!(verify_non_membership::<_, FULL_DEPTH, TestPoseidon>(
+            root,
+            nullifiers[2],
+            &proof
+        ));
+        // The largest key has a leaf too.
+        assert!(tree.insert(-Fr::one(), Fr::one()).is_ok());
+    }
+}
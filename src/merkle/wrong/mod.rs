@@ -0,0 +1,281 @@
+pub mod sparse;
+
+use super::native::MerkleProof;
+use crate::hasher::Hasher;
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::Error;
+use maingate::{
+    AssignedCondition, AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RegionCtx,
+    UnassignedValue,
+};
+use std::marker::PhantomData;
+
+/// `MainGate` counterpart of `MerkleProof::root`.
+pub struct MerklePathChip<F: FieldExt, const DEPTH: usize, H>
+where
+    H: Hasher<F, 5>,
+{
+    main_gate_config: MainGateConfig,
+    _hasher: PhantomData<H>,
+}
+
+impl<F: FieldExt, const DEPTH: usize, H> MerklePathChip<F, DEPTH, H>
+where
+    H: Hasher<F, 5>,
+{
+    pub fn new(main_gate_config: MainGateConfig) -> Self {
+        Self {
+            main_gate_config,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn hash(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        left: &AssignedValue<F>,
+        right: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let zero = main_gate.assign_constant(ctx, F::zero())?;
+
+        let inputs = [
+            left.clone(),
+            right.clone(),
+            zero.clone(),
+            zero.clone(),
+            zero,
+        ];
+        let out = H::permute_assigned(&self.main_gate_config, ctx, inputs)?;
+        Ok(out[0].clone())
+    }
+
+    /// Assigns the siblings and path bits of `proof` and returns the root they
+    /// lead to from `leaf`. Path bits are constrained to be boolean.
+    pub fn root(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        leaf: &AssignedValue<F>,
+        proof: Option<MerkleProof<F, DEPTH>>,
+    ) -> Result<AssignedValue<F>, Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+
+        let mut siblings = Vec::new();
+        let mut path = Vec::new();
+        for i in 0..DEPTH {
+            let sibling = proof.map(|p| p.siblings[i]);
+            let bit = proof.map(|p| if p.path[i] { F::one() } else { F::zero() });
+            siblings.push(main_gate.assign_value(ctx, &UnassignedValue::from(sibling))?);
+            path.push(main_gate.assign_bit(ctx, &UnassignedValue::from(bit))?);
+        }
+
+        self.root_from_path(ctx, leaf, &siblings, &path)
+    }
+
+    /// Root of an already assigned path, `path[i]` is set where the node at
+    /// level `i` is a right child.
+    pub fn root_from_path(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        leaf: &AssignedValue<F>,
+        siblings: &[AssignedValue<F>],
+        path: &[AssignedCondition<F>],
+    ) -> Result<AssignedValue<F>, Error> {
+        assert_eq!(siblings.len(), DEPTH);
+        assert_eq!(path.len(), DEPTH);
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+
+        let mut node = leaf.clone();
+        for (sibling, bit) in siblings.iter().zip(path) {
+            // When the bit is set the current node is the right child.
+            let left = main_gate.select(ctx, sibling, &node, bit)?;
+            let right = main_gate.select(ctx, &node, sibling, bit)?;
+            node = self.hash(ctx, &left, &right)?;
+        }
+
+        Ok(node)
+    }
+
+    /// Root `siblings` lead to from `leaf` at the constant position `index`, so
+    /// the path bits are fixed by the circuit rather than witnessed.
+    pub fn root_at(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        leaf: &AssignedValue<F>,
+        index: usize,
+        siblings: Option<[F; DEPTH]>,
+    ) -> Result<AssignedValue<F>, Error> {
+        assert!(index < 1 << DEPTH);
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+
+        let mut node = leaf.clone();
+        for level in 0..DEPTH {
+            let sibling = siblings.map(|s| s[level]);
+            let sibling = main_gate.assign_value(ctx, &UnassignedValue::from(sibling))?;
+            node = if (index >> level) & 1 == 1 {
+                self.hash(ctx, &sibling, &node)?
+            } else {
+                self.hash(ctx, &node, &sibling)?
+            };
+        }
+
+        Ok(node)
+    }
+
+    /// Root of the tree over `leaves` with zero past the last one, the
+    /// counterpart of `MerkleTree::root`.
+    pub fn tree_root(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        leaves: &[AssignedValue<F>],
+    ) -> Result<AssignedValue<F>, Error> {
+        assert!(leaves.len() <= 1 << DEPTH);
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let zero = main_gate.assign_constant(ctx, F::zero())?;
+
+        let mut level = leaves.to_vec();
+        level.resize(1 << DEPTH, zero);
+        for _ in 0..DEPTH {
+            let mut next = Vec::new();
+            for pair in level.chunks(2) {
+                next.push(self.hash(ctx, &pair[0], &pair[1])?);
+            }
+            level = next;
+        }
+
+        Ok(level[0].clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::merkle::native::MerkleTree;
+    use crate::poseidon::{native::Poseidon, params::bn254_5x5::Params5x5Bn254};
+    use crate::rescue::{native::RescuePrime, params as rescue_params};
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Column, ConstraintSystem, Instance},
+    };
+
+    const DEPTH: usize = 3;
+
+    type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
+
+    type TestRescuePrime = RescuePrime<Fr, 5, rescue_params::Params5x5Bn254>;
+
+    type TestMerkleTree = MerkleTree<Fr, DEPTH, TestPoseidon>;
+
+    #[derive(Clone)]
+    struct MerkleTesterConfig {
+        main_gate_config: MainGateConfig,
+        results: Column<Instance>,
+    }
+
+    struct MerkleTester<H: Hasher<Fr, 5>> {
+        leaf: Option<Fr>,
+        proof: Option<MerkleProof<Fr, DEPTH>>,
+        _hasher: PhantomData<H>,
+    }
+
+    impl<H: Hasher<Fr, 5>> MerkleTester<H> {
+        fn new(leaf: Fr, proof: MerkleProof<Fr, DEPTH>) -> Self {
+            Self {
+                leaf: Some(leaf),
+                proof: Some(proof),
+                _hasher: PhantomData,
+            }
+        }
+    }
+
+    impl<H: Hasher<Fr, 5>> Circuit<Fr> for MerkleTester<H> {
+        type Config = MerkleTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                leaf: None,
+                proof: None,
+                _hasher: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let main_gate_config = MainGate::<Fr>::configure(meta);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                main_gate_config,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.main_gate_config.clone());
+
+            let root = layouter.assign_region(
+                || "merkle_path",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    let leaf = main_gate.assign_value(ctx, &UnassignedValue::from(self.leaf))?;
+
+                    let merkle =
+                        MerklePathChip::<Fr, DEPTH, H>::new(config.main_gate_config.clone());
+                    merkle.root(ctx, &leaf, self.proof)
+                },
+            )?;
+
+            layouter.constrain_instance(root.cell(), config.results, 0)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_match_native_root() {
+        let leaves: Vec<Fr> = (1..7u64).map(|n| Fr::from(n)).collect();
+        let tree = TestMerkleTree::new(&leaves);
+
+        let merkle_tester = MerkleTester::<TestPoseidon>::new(leaves[5], tree.proof(5));
+
+        let k = 13;
+        let prover = MockProver::run(k, &merkle_tester, vec![vec![tree.root()]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_fail_on_wrong_leaf() {
+        let leaves: Vec<Fr> = (1..7u64).map(|n| Fr::from(n)).collect();
+        let tree = TestMerkleTree::new(&leaves);
+
+        let merkle_tester = MerkleTester::<TestPoseidon>::new(leaves[4], tree.proof(5));
+
+        let k = 13;
+        let prover = MockProver::run(k, &merkle_tester, vec![vec![tree.root()]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn should_match_native_root_with_rescue_prime() {
+        let leaves: Vec<Fr> = (1..7u64).map(|n| Fr::from(n)).collect();
+        let tree = MerkleTree::<Fr, DEPTH, TestRescuePrime>::new(&leaves);
+
+        let merkle_tester = MerkleTester::<TestRescuePrime>::new(leaves[5], tree.proof(5));
+
+        let k = 13;
+        let prover = MockProver::run(k, &merkle_tester, vec![vec![tree.root()]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let poseidon_root = TestMerkleTree::new(&leaves).root();
+        let prover = MockProver::run(k, &merkle_tester, vec![vec![poseidon_root]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,480 @@
+use super::MerklePathChip;
+use crate::hasher::Hasher;
+use crate::merkle::native::MerkleProof;
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::Error;
+use maingate::{
+    AssignedCondition, AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RegionCtx,
+    UnassignedValue,
+};
+use std::marker::PhantomData;
+
+/// In-circuit verifier for the native `SparseMerkleTree`. The path of a key is
+/// its bit decomposition, which also constrains the key to `DEPTH` bits, so a
+/// proof can only ever speak about the leaf of that key. At the full bit size
+/// of `F` the bits are also checked to be below the modulus, as `p + key` would
+/// give a key a second path otherwise.
+pub struct SparseMerkleChip<F: FieldExt, const DEPTH: usize, H>
+where
+    H: Hasher<F, 5>,
+{
+    main_gate_config: MainGateConfig,
+    _hasher: PhantomData<H>,
+}
+
+impl<F: FieldExt, const DEPTH: usize, H> SparseMerkleChip<F, DEPTH, H>
+where
+    H: Hasher<F, 5>,
+{
+    pub fn new(main_gate_config: MainGateConfig) -> Self {
+        Self {
+            main_gate_config,
+            _hasher: PhantomData,
+        }
+    }
+
+    /// In-circuit counterpart of `native::sparse::leaf_hash`.
+    pub fn leaf_hash(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        key: &AssignedValue<F>,
+        value: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let zero = main_gate.assign_constant(ctx, F::zero())?;
+        let one = main_gate.assign_constant(ctx, F::one())?;
+
+        let inputs = [key.clone(), value.clone(), one, zero.clone(), zero];
+        Ok(H::permute_assigned(&self.main_gate_config, ctx, inputs)?[0].clone())
+    }
+
+    /// Constrains the little-endian `bits` to be at most `p - 1`. From the top
+    /// bit down, `equal` is set while the bits match those of `p - 1`, and a
+    /// bit may only exceed its counterpart once they no longer do.
+    fn assert_canonical(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        bits: &[AssignedCondition<F>],
+    ) -> Result<(), Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let max = (-F::one()).to_repr();
+        let max = max.as_ref();
+
+        let mut equal = main_gate.assign_constant(ctx, F::one())?;
+        for (i, bit) in bits.iter().enumerate().rev() {
+            let bit = bit.clone().into();
+            if (max[i / 8] >> (i % 8)) & 1 == 1 {
+                equal = main_gate.mul(ctx, &equal, &bit)?;
+            } else {
+                let over = main_gate.mul(ctx, &equal, &bit)?;
+                main_gate.assert_zero(ctx, &over)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Path bits of `key` and the assigned siblings of `proof`. The path bits of
+    /// the proof itself are ignored.
+    fn assign_path(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        key: &AssignedValue<F>,
+        proof: Option<MerkleProof<F, DEPTH>>,
+    ) -> Result<(Vec<AssignedValue<F>>, Vec<AssignedCondition<F>>), Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let path = main_gate.to_bits(ctx, key, DEPTH)?;
+        if DEPTH == F::NUM_BITS as usize {
+            self.assert_canonical(ctx, &path)?;
+        }
+
+        let mut siblings = Vec::new();
+        for i in 0..DEPTH {
+            let sibling = proof.map(|p| p.siblings[i]);
+            siblings.push(main_gate.assign_value(ctx, &UnassignedValue::from(sibling))?);
+        }
+
+        Ok((siblings, path))
+    }
+
+    /// Constrains `key` to hold `value` in the tree of `root`.
+    pub fn verify_membership(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        root: &AssignedValue<F>,
+        key: &AssignedValue<F>,
+        value: &AssignedValue<F>,
+        proof: Option<MerkleProof<F, DEPTH>>,
+    ) -> Result<(), Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let path_chip = MerklePathChip::<F, DEPTH, H>::new(self.main_gate_config.clone());
+
+        let (siblings, path) = self.assign_path(ctx, key, proof)?;
+        let leaf = self.leaf_hash(ctx, key, value)?;
+        let computed = path_chip.root_from_path(ctx, &leaf, &siblings, &path)?;
+        main_gate.assert_equal(ctx, &computed, root)
+    }
+
+    /// Constrains the leaf of `key` to be empty in the tree of `root`.
+    pub fn verify_non_membership(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        root: &AssignedValue<F>,
+        key: &AssignedValue<F>,
+        proof: Option<MerkleProof<F, DEPTH>>,
+    ) -> Result<(), Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let path_chip = MerklePathChip::<F, DEPTH, H>::new(self.main_gate_config.clone());
+
+        let (siblings, path) = self.assign_path(ctx, key, proof)?;
+        let empty = main_gate.assign_constant(ctx, F::zero())?;
+        let computed = path_chip.root_from_path(ctx, &empty, &siblings, &path)?;
+        main_gate.assert_equal(ctx, &computed, root)
+    }
+
+    /// Constrains the transition from `old_root` to `new_root` to change only
+    /// the leaf of `key`, from `old_value` to `new_value`. `old_value` is `None`
+    /// when the key is inserted into an empty leaf.
+    pub fn verify_update(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        old_root: &AssignedValue<F>,
+        new_root: &AssignedValue<F>,
+        key: &AssignedValue<F>,
+        old_value: Option<&AssignedValue<F>>,
+        new_value: &AssignedValue<F>,
+        proof: Option<MerkleProof<F, DEPTH>>,
+    ) -> Result<(), Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let path_chip = MerklePathChip::<F, DEPTH, H>::new(self.main_gate_config.clone());
+
+        // Both roots are computed over the same siblings and path.
+        let (siblings, path) = self.assign_path(ctx, key, proof)?;
+        let old_leaf = match old_value {
+            Some(value) => self.leaf_hash(ctx, key, value)?,
+            None => main_gate.assign_constant(ctx, F::zero())?,
+        };
+        let computed_old = path_chip.root_from_path(ctx, &old_leaf, &siblings, &path)?;
+        main_gate.assert_equal(ctx, &computed_old, old_root)?;
+
+        let new_leaf = self.leaf_hash(ctx, key, new_value)?;
+        let computed_new = path_chip.root_from_path(ctx, &new_leaf, &siblings, &path)?;
+        main_gate.assert_equal(ctx, &computed_new, new_root)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::merkle::native::sparse::SparseMerkleTree;
+    use crate::poseidon::{native::Poseidon, params::bn254_5x5::Params5x5Bn254};
+    use ff::PrimeField;
+    use halo2_proofs::{
+        arithmetic::Field,
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Column, ConstraintSystem, Instance},
+    };
+
+    const DEPTH: usize = 8;
+
+    type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
+
+    type TestSparseMerkleTree = SparseMerkleTree<Fr, DEPTH, TestPoseidon>;
+
+    type TestSparseMerkleChip = SparseMerkleChip<Fr, DEPTH, TestPoseidon>;
+
+    #[derive(Clone, Copy)]
+    enum Statement {
+        Membership,
+        NonMembership,
+        Insert,
+        Update,
+    }
+
+    #[derive(Clone)]
+    struct SparseMerkleTesterConfig {
+        main_gate_config: MainGateConfig,
+        results: Column<Instance>,
+    }
+
+    /// Proves `statement` about `key`, the roots are the public inputs: the root
+    /// of the tree, or the old and the new root of an update.
+    struct SparseMerkleTester {
+        statement: Statement,
+        roots: Option<[Fr; 2]>,
+        key: Option<Fr>,
+        old_value: Option<Fr>,
+        value: Option<Fr>,
+        proof: Option<MerkleProof<Fr, DEPTH>>,
+    }
+
+    impl Circuit<Fr> for SparseMerkleTester {
+        type Config = SparseMerkleTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                statement: self.statement,
+                roots: None,
+                key: None,
+                old_value: None,
+                value: None,
+                proof: None,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let main_gate_config = MainGate::<Fr>::configure(meta);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                main_gate_config,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.main_gate_config.clone());
+
+            let (root, new_root) = layouter.assign_region(
+                || "sparse_merkle",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    let chip = TestSparseMerkleChip::new(config.main_gate_config.clone());
+
+                    let key = main_gate.assign_value(ctx, &UnassignedValue::from(self.key))?;
+                    let value = main_gate.assign_value(ctx, &UnassignedValue::from(self.value))?;
+                    let root = self.roots.map(|r| r[0]);
+                    let root = main_gate.assign_value(ctx, &UnassignedValue::from(root))?;
+                    let new_root = self.roots.map(|r| r[1]);
+                    let new_root = main_gate.assign_value(ctx, &UnassignedValue::from(new_root))?;
+
+                    match self.statement {
+                        Statement::Membership => {
+                            chip.verify_membership(ctx, &root, &key, &value, self.proof)?
+                        }
+                        Statement::NonMembership => {
+                            chip.verify_non_membership(ctx, &root, &key, self.proof)?
+                        }
+                        Statement::Insert => chip
+                            .verify_update(ctx, &root, &new_root, &key, None, &value, self.proof)?,
+                        Statement::Update => {
+                            let old_value = main_gate
+                                .assign_value(ctx, &UnassignedValue::from(self.old_value))?;
+                            chip.verify_update(
+                                ctx,
+                                &root,
+                                &new_root,
+                                &key,
+                                Some(&old_value),
+                                &value,
+                                self.proof,
+                            )?
+                        }
+                    }
+
+                    Ok((root, new_root))
+                },
+            )?;
+
+            layouter.constrain_instance(root.cell(), config.results, 0)?;
+            layouter.constrain_instance(new_root.cell(), config.results, 1)?;
+            Ok(())
+        }
+    }
+
+    fn tree() -> TestSparseMerkleTree {
+        let mut tree = TestSparseMerkleTree::new();
+        tree.insert(Fr::from(3), Fr::from(30)).unwrap();
+        tree.insert(Fr::from(200), Fr::from(2000)).unwrap();
+        tree.insert(Fr::from(7), Fr::from(70)).unwrap();
+        tree
+    }
+
+    fn run(mut tester: SparseMerkleTester, roots: [Fr; 2]) -> MockProver<Fr> {
+        let k = 13;
+        tester.roots = Some(roots);
+        MockProver::run(k, &tester, vec![roots.to_vec()]).unwrap()
+    }
+
+    #[test]
+    fn should_verify_membership() {
+        let tree = tree();
+        let key = Fr::from(200);
+        let tester = SparseMerkleTester {
+            statement: Statement::Membership,
+            roots: None,
+            key: Some(key),
+            old_value: None,
+            value: Some(Fr::from(2000)),
+            proof: Some(tree.proof(&key).unwrap()),
+        };
+        // The second root is unused outside updates.
+        let prover = run(tester, [tree.root(), tree.root()]);
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_verify_non_membership() {
+        let tree = tree();
+        let key = Fr::from(4);
+        let tester = SparseMerkleTester {
+            statement: Statement::NonMembership,
+            roots: None,
+            key: Some(key),
+            old_value: None,
+            value: Some(Fr::zero()),
+            proof: Some(tree.proof(&key).unwrap()),
+        };
+        let prover = run(tester, [tree.root(), tree.root()]);
+        assert_eq!(prover.verify(), Ok(()));
+
+        // A key in the tree can not be shown absent.
+        let key = Fr::from(7);
+        let tester = SparseMerkleTester {
+            statement: Statement::NonMembership,
+            roots: None,
+            key: Some(key),
+            old_value: None,
+            value: Some(Fr::zero()),
+            proof: Some(tree.proof(&key).unwrap()),
+        };
+        let prover = run(tester, [tree.root(), tree.root()]);
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn should_verify_insert_and_update() {
+        let mut tree = tree();
+        let key = Fr::from(9);
+
+        let old_root = tree.root();
+        let proof = tree.proof(&key).unwrap();
+        let new_root = tree.insert(key, Fr::from(90)).unwrap();
+        let tester = SparseMerkleTester {
+            statement: Statement::Insert,
+            roots: None,
+            key: Some(key),
+            old_value: None,
+            value: Some(Fr::from(90)),
+            proof: Some(proof),
+        };
+        let prover = run(tester, [old_root, new_root]);
+        assert_eq!(prover.verify(), Ok(()));
+
+        let old_root = new_root;
+        let proof = tree.proof(&key).unwrap();
+        let new_root = tree.insert(key, Fr::from(91)).unwrap();
+        let tester = SparseMerkleTester {
+            statement: Statement::Update,
+            roots: None,
+            key: Some(key),
+            old_value: Some(Fr::from(90)),
+            value: Some(Fr::from(91)),
+            proof: Some(proof),
+        };
+        let prover = run(tester, [old_root, new_root]);
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_fail_on_key_over_depth() {
+        let tree = tree();
+        // `256 + 4` has the same low bits as `4`, which is absent.
+        let key = Fr::from(260);
+        let tester = SparseMerkleTester {
+            statement: Statement::NonMembership,
+            roots: None,
+            key: Some(key),
+            old_value: None,
+            value: Some(Fr::zero()),
+            proof: Some(tree.proof(&Fr::from(4)).unwrap()),
+        };
+        let prover = run(tester, [tree.root(), tree.root()]);
+        assert!(prover.verify().is_err());
+    }
+
+    /// Assigns `bits` and checks them with `assert_canonical`.
+    struct CanonicalTester {
+        bits: Vec<bool>,
+    }
+
+    impl Circuit<Fr> for CanonicalTester {
+        type Config = MainGateConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                bits: self.bits.clone(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            MainGate::<Fr>::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.clone());
+            layouter.assign_region(
+                || "canonical",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    let chip = TestSparseMerkleChip::new(config.clone());
+
+                    let mut bits = Vec::new();
+                    for bit in &self.bits {
+                        let bit = if *bit { Fr::one() } else { Fr::zero() };
+                        bits.push(main_gate.assign_bit(ctx, &UnassignedValue::from(Some(bit)))?);
+                    }
+                    chip.assert_canonical(ctx, &bits)
+                },
+            )
+        }
+    }
+
+    /// Little-endian bits of the integer `repr + carry`.
+    fn bits_of(repr: &[u8], carry: u8) -> Vec<bool> {
+        let mut bytes = repr.to_vec();
+        let mut carry = carry as u16;
+        for byte in bytes.iter_mut() {
+            let sum = *byte as u16 + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        (0..Fr::NUM_BITS as usize)
+            .map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+            .collect()
+    }
+
+    #[test]
+    fn should_reject_non_canonical_bits() {
+        let max = (-Fr::one()).to_repr();
+        let k = 10;
+
+        let tester = CanonicalTester {
+            bits: bits_of(max.as_ref(), 0),
+        };
+        let prover = MockProver::run(k, &tester, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // The bits of `p` compose to zero, but they are not its path.
+        let tester = CanonicalTester {
+            bits: bits_of(max.as_ref(), 1),
+        };
+        let prover = MockProver::run(k, &tester, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -1,6 +1,7 @@
-mod native;
+pub mod native;
 pub mod params;
 pub mod sponge;
+pub mod wrong;
 
 use halo2_proofs::{
     arithmetic::FieldExt,
@@ -8,21 +9,104 @@ use halo2_proofs::{
     plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector, VirtualCells},
     poly::Rotation,
 };
-use params::RoundParams;
+use params::{PolySbox, RoundParams};
 use std::marker::PhantomData;
 
+/// How `PoseidonChip` lays out the partial rounds, trading columns for rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoseidonLayout {
+    /// Partial rounds computed in a single row. Every round past the first
+    /// takes one more advice column, for lane `0` of its input, and `WIDTH`
+    /// more fixed columns, for its round constants.
+    pub partial_rounds_per_row: usize,
+    /// Witnesses the square of every S-box input, so the gates take the S-box
+    /// from it with `sbox_expr_from_square`: `x^5` drops from degree 5 to 3.
+    /// Takes `max(WIDTH, partial_rounds_per_row)` more advice columns.
+    pub square_sbox_inputs: bool,
+}
+
+impl Default for PoseidonLayout {
+    fn default() -> Self {
+        Self {
+            partial_rounds_per_row: 1,
+            square_sbox_inputs: false,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct PoseidonConfig<const WIDTH: usize> {
     state: [Column<Advice>; WIDTH],
     round_constants: [Column<Fixed>; WIDTH],
-    mds: [[Column<Fixed>; WIDTH]; WIDTH],
     full_round_selector: Selector,
     partial_round_selector: Selector,
+    /// Lane `0` at the start of every partial round of a row but the first.
+    packed_state: Vec<Column<Advice>>,
+    packed_round_constants: Vec<[Column<Fixed>; WIDTH]>,
+    packed_round_selector: Option<Selector>,
+    /// Squares of the S-box inputs of lane `i` in full rounds and of round `i`
+    /// of a row in partial rounds, empty unless `square_sbox_inputs` is set.
+    sbox_squares: Vec<Column<Advice>>,
+}
+
+/// Lanes of the state as linear combinations of `terms`, so that the
+/// expressions of a row grow linearly with the rounds packed into it.
+struct LinearState<F: FieldExt, const WIDTH: usize> {
+    terms: Vec<Expression<F>>,
+    lanes: [Vec<F>; WIDTH],
+}
+
+impl<F: FieldExt, const WIDTH: usize> LinearState<F, WIDTH> {
+    fn new() -> Self {
+        Self {
+            terms: Vec::new(),
+            lanes: [(); WIDTH].map(|_| Vec::new()),
+        }
+    }
+
+    /// Adds `term` to `lane`.
+    fn add(&mut self, lane: usize, term: Expression<F>) {
+        self.terms.push(term);
+        for (i, coeffs) in self.lanes.iter_mut().enumerate() {
+            coeffs.push(if i == lane { F::one() } else { F::zero() });
+        }
+    }
+
+    /// Replaces `lane` with `term`.
+    fn set(&mut self, lane: usize, term: Expression<F>) {
+        for coeff in self.lanes[lane].iter_mut() {
+            *coeff = F::zero();
+        }
+        self.add(lane, term);
+    }
+
+    fn expr(&self, lane: usize) -> Expression<F> {
+        self.lanes[lane]
+            .iter()
+            .zip(self.terms.iter())
+            .filter(|(coeff, _)| **coeff != F::zero())
+            .fold(Expression::Constant(F::zero()), |acc, (coeff, term)| {
+                acc + term.clone() * Expression::Constant(*coeff)
+            })
+    }
+
+    fn apply_mds(&mut self, mds: &[[F; WIDTH]; WIDTH]) {
+        let len = self.terms.len();
+        self.lanes = mds.map(|row| {
+            let mut coeffs = vec![F::zero(); len];
+            for (mds_ij, lane) in row.iter().zip(self.lanes.iter()) {
+                for (coeff, lane_coeff) in coeffs.iter_mut().zip(lane.iter()) {
+                    *coeff += *mds_ij * *lane_coeff;
+                }
+            }
+            coeffs
+        });
+    }
 }
 
 pub struct PoseidonChip<F: FieldExt, const WIDTH: usize, P>
 where
-    P: RoundParams<F, WIDTH>,
+    P: RoundParams<F, WIDTH> + PolySbox,
 {
     inputs: [AssignedCell<F, F>; WIDTH],
     _params: PhantomData<P>,
@@ -30,9 +114,9 @@ where
 
 impl<F: FieldExt, const WIDTH: usize, P> PoseidonChip<F, WIDTH, P>
 where
-    P: RoundParams<F, WIDTH>,
+    P: RoundParams<F, WIDTH> + PolySbox,
 {
-    fn new(inputs: [AssignedCell<F, F>; WIDTH]) -> Self {
+    pub fn new(inputs: [AssignedCell<F, F>; WIDTH]) -> Self {
         PoseidonChip {
             inputs,
             _params: PhantomData,
@@ -89,27 +173,6 @@ where
         Ok(round_cells.map(|item| item.unwrap()))
     }
 
-    fn load_mds(
-        config: &PoseidonConfig<WIDTH>,
-        region: &mut Region<'_, F>,
-        round: usize,
-        mds: &[[F; WIDTH]; WIDTH],
-    ) -> Result<[[AssignedCell<F, F>; WIDTH]; WIDTH], Error> {
-        let mut mds_cells: [[Option<AssignedCell<F, F>>; WIDTH]; WIDTH] =
-            [[(); WIDTH]; WIDTH].map(|vec| vec.map(|_| None));
-        for i in 0..WIDTH {
-            for j in 0..WIDTH {
-                mds_cells[i][j] = Some(region.assign_fixed(
-                    || "mds",
-                    config.mds[i][j],
-                    round,
-                    || Ok(mds[i][j]),
-                )?);
-            }
-        }
-        Ok(mds_cells.map(|vec| vec.map(|item| item.unwrap())))
-    }
-
     fn apply_round_constants(
         state_cells: &[AssignedCell<F, F>; WIDTH],
         round_const_cells: &[AssignedCell<F, F>; WIDTH],
@@ -126,16 +189,13 @@ where
         next_state
     }
 
-    fn apply_mds(
-        next_state: &[Option<F>; WIDTH],
-        mds_cells: &[[AssignedCell<F, F>; WIDTH]; WIDTH],
-    ) -> [Option<F>; WIDTH] {
+    fn apply_mds(next_state: &[Option<F>; WIDTH], mds: &[[F; WIDTH]; WIDTH]) -> [Option<F>; WIDTH] {
         let mut new_state = [Some(F::zero()); WIDTH];
         // Compute mds matrix
         for i in 0..WIDTH {
             for j in 0..WIDTH {
-                let mds_ij = &mds_cells[i][j];
-                let m_product = next_state[j].and_then(|s| mds_ij.value().map(|&m| s * m));
+                let mds_ij = mds[i][j];
+                let m_product = next_state[j].map(|s| s * mds_ij);
                 new_state[i] = new_state[i].and_then(|a| m_product.map(|b| a + b));
             }
         }
@@ -157,22 +217,61 @@ where
         exprs
     }
 
+    /// The MDS matrix is the same in every round, so its entries are constants
+    /// of the gates rather than fixed columns.
     fn apply_mds_expr(
-        v_cells: &mut VirtualCells<F>,
         exprs: &[Expression<F>; WIDTH],
-        mds: &[[Column<Fixed>; WIDTH]; WIDTH],
+        mds: &[[F; WIDTH]; WIDTH],
     ) -> [Expression<F>; WIDTH] {
         let mut new_exprs = [(); WIDTH].map(|_| Expression::Constant(F::zero()));
         // Mat mul with MDS
         for i in 0..WIDTH {
             for j in 0..WIDTH {
-                let mds_ij = v_cells.query_fixed(mds[i][j], Rotation::cur());
+                let mds_ij = Expression::Constant(mds[i][j]);
                 new_exprs[i] = new_exprs[i].clone() + (exprs[j].clone() * mds_ij);
             }
         }
         new_exprs
     }
 
+    /// `sbox_expr` of `exp`, taken from its square in `square` when the layout
+    /// witnesses one. The constraint on the square is pushed to `constraints`.
+    fn sbox_expr(
+        v_cells: &mut VirtualCells<F>,
+        square: Option<&Column<Advice>>,
+        exp: Expression<F>,
+        constraints: &mut Vec<Expression<F>>,
+    ) -> Expression<F> {
+        match square {
+            Some(column) => {
+                let square = v_cells.query_advice(*column, Rotation::cur());
+                constraints.push(square.clone() - exp.clone() * exp.clone());
+                P::sbox_expr_from_square(exp, square)
+            }
+            None => P::sbox_expr(exp),
+        }
+    }
+
+    /// Witnesses the square of the S-box input `exp` in column `index` of
+    /// `sbox_squares`, if the layout has it.
+    fn assign_sbox_square(
+        config: &PoseidonConfig<WIDTH>,
+        region: &mut Region<'_, F>,
+        index: usize,
+        row: usize,
+        exp: Option<F>,
+    ) -> Result<(), Error> {
+        if let Some(column) = config.sbox_squares.get(index) {
+            region.assign_advice(
+                || "sbox_square",
+                *column,
+                row,
+                || exp.map(|x| x * x).ok_or(Error::Synthesis),
+            )?;
+        }
+        Ok(())
+    }
+
     fn full_round(
         config: &PoseidonConfig<WIDTH>,
         region: &mut Region<'_, F>,
@@ -189,15 +288,14 @@ where
             // Assign round constants
             let round_const_cells =
                 Self::load_round_constants(&config, region, round, round_constants)?;
-            // Assign mds matrix
-            let mds_cells = Self::load_mds(&config, region, round, &mds)?;
 
             let mut next_state = Self::apply_round_constants(&state_cells, &round_const_cells);
             for i in 0..WIDTH {
+                Self::assign_sbox_square(config, region, i, round, next_state[i])?;
                 next_state[i] = next_state[i].map(|s| P::sbox_f(s));
             }
 
-            next_state = Self::apply_mds(&next_state, &mds_cells);
+            next_state = Self::apply_mds(&next_state, mds);
 
             // Assign next state
             for i in 0..WIDTH {
@@ -212,6 +310,8 @@ where
         Ok(state_cells)
     }
 
+    /// Partial rounds, `config.packed_state.len() + 1` to a row. The rounds
+    /// left over are computed one to a row.
     fn partial_round(
         config: &PoseidonConfig<WIDTH>,
         region: &mut Region<'_, F>,
@@ -220,27 +320,63 @@ where
         mds: &[[F; WIDTH]; WIDTH],
         prev_state: &[AssignedCell<F, F>; WIDTH],
     ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
-        let mut state_cells = Self::copy_state(&config, region, 0, &prev_state)?;
-        for round in 0..num_rounds {
-            config.partial_round_selector.enable(region, round)?;
+        let rounds_per_row = config.packed_state.len() + 1;
+        let packed_rows = num_rounds / rounds_per_row;
+        let num_rows = packed_rows + num_rounds % rounds_per_row;
 
-            // Assign round constants
-            let round_const_cells =
-                Self::load_round_constants(&config, region, round, round_constants)?;
-            // Assign mds matrix
-            let mds_cells = Self::load_mds(&config, region, round, &mds)?;
-
-            let mut next_state = Self::apply_round_constants(&state_cells, &round_const_cells);
-            next_state[0] = next_state[0].map(|x| P::sbox_f(x));
-
-            next_state = Self::apply_mds(&next_state, &mds_cells);
+        let mut state_cells = Self::copy_state(&config, region, 0, &prev_state)?;
+        let mut next_state = state_cells.clone().map(|cell| cell.value().cloned());
+        let mut round = 0;
+        for row in 0..num_rows {
+            let rounds_in_row = match config.packed_round_selector {
+                Some(selector) if row < packed_rows => {
+                    selector.enable(region, row)?;
+                    rounds_per_row
+                }
+                _ => {
+                    config.partial_round_selector.enable(region, row)?;
+                    1
+                }
+            };
+
+            for packed in 0..rounds_in_row {
+                let constant_columns = match packed {
+                    0 => &config.round_constants,
+                    _ => {
+                        region.assign_advice(
+                            || "lane_zero",
+                            config.packed_state[packed - 1],
+                            row,
+                            || next_state[0].ok_or(Error::Synthesis),
+                        )?;
+                        &config.packed_round_constants[packed - 1]
+                    }
+                };
+
+                // Assign round constants
+                for i in 0..WIDTH {
+                    let round_constant = round_constants[round * WIDTH + i];
+                    region.assign_fixed(
+                        || "round_constant",
+                        constant_columns[i],
+                        row,
+                        || Ok(round_constant),
+                    )?;
+                    next_state[i] = next_state[i].map(|s| s + round_constant);
+                }
+                Self::assign_sbox_square(config, region, packed, row, next_state[0])?;
+                next_state[0] = next_state[0].map(|x| P::sbox_f(x));
+
+                next_state = Self::apply_mds(&next_state, mds);
+                round += 1;
+            }
 
             // Assign next state
             for i in 0..WIDTH {
                 state_cells[i] = region.assign_advice(
                     || "state",
                     config.state[i],
-                    round + 1,
+                    row + 1,
                     || next_state[i].ok_or(Error::Synthesis),
                 )?;
             }
@@ -251,25 +387,46 @@ where
 
 impl<F: FieldExt, const WIDTH: usize, P> PoseidonChip<F, WIDTH, P>
 where
-    P: RoundParams<F, WIDTH>,
+    P: RoundParams<F, WIDTH> + PolySbox,
 {
     pub fn configure(meta: &mut ConstraintSystem<F>) -> PoseidonConfig<WIDTH> {
+        Self::configure_with_layout(meta, PoseidonLayout::default())
+    }
+
+    /// Configures the chip with `layout.partial_rounds_per_row` partial
+    /// rounds to a row. Lane `0` is witnessed at the start of every round, so
+    /// the gates keep the degree of the S-box however many rounds a row has,
+    /// that of `sbox_expr` or, with `layout.square_sbox_inputs`, of
+    /// `sbox_expr_from_square`.
+    pub fn configure_with_layout(
+        meta: &mut ConstraintSystem<F>,
+        layout: PoseidonLayout,
+    ) -> PoseidonConfig<WIDTH> {
+        assert!(layout.partial_rounds_per_row > 0);
         let state = [(); WIDTH].map(|_| {
             let column = meta.advice_column();
             meta.enable_equality(column);
             column
         });
         let round_constants = [(); WIDTH].map(|_| meta.fixed_column());
-        let mds = [[(); WIDTH]; WIDTH].map(|vec| vec.map(|_| meta.fixed_column()));
+        let mds = P::mds();
         let full_round_selector = meta.selector();
         let partial_round_selector = meta.selector();
+        let sbox_squares: Vec<Column<Advice>> = if layout.square_sbox_inputs {
+            let columns = WIDTH.max(layout.partial_rounds_per_row);
+            (0..columns).map(|_| meta.advice_column()).collect()
+        } else {
+            Vec::new()
+        };
 
         meta.create_gate("poseidon_round", |v_cells| {
+            let mut square_exprs = Vec::new();
             let mut exprs = Self::apply_round_constants_expr(v_cells, &state, &round_constants);
             for i in 0..WIDTH {
-                exprs[i] = P::sbox_expr(exprs[i].clone());
+                let square = sbox_squares.get(i);
+                exprs[i] = Self::sbox_expr(v_cells, square, exprs[i].clone(), &mut square_exprs);
             }
-            exprs = Self::apply_mds_expr(v_cells, &exprs, &mds);
+            exprs = Self::apply_mds_expr(&exprs, &mds);
 
             let s_cells = v_cells.query_selector(full_round_selector);
             // It should be equal to the state in next row
@@ -277,14 +434,17 @@ where
                 let next_state = v_cells.query_advice(state[i], Rotation::next());
                 exprs[i] = s_cells.clone() * (exprs[i].clone() - next_state);
             }
-            exprs
+            let square_exprs = square_exprs.into_iter().map(|expr| s_cells.clone() * expr);
+            exprs.into_iter().chain(square_exprs).collect::<Vec<_>>()
         });
 
         meta.create_gate("partial_round", |v_cells| {
+            let mut square_exprs = Vec::new();
             let mut exprs = Self::apply_round_constants_expr(v_cells, &state, &round_constants);
-            exprs[0] = P::sbox_expr(exprs[0].clone());
+            let square = sbox_squares.get(0);
+            exprs[0] = Self::sbox_expr(v_cells, square, exprs[0].clone(), &mut square_exprs);
 
-            exprs = Self::apply_mds_expr(v_cells, &exprs, &mds);
+            exprs = Self::apply_mds_expr(&exprs, &mds);
 
             let s_cells = v_cells.query_selector(partial_round_selector);
             // It should be equal to the state in next row
@@ -292,20 +452,76 @@ where
                 let next_state = v_cells.query_advice(state[i], Rotation::next());
                 exprs[i] = s_cells.clone() * (exprs[i].clone() - next_state);
             }
-
-            exprs
+            let square_exprs = square_exprs.into_iter().map(|expr| s_cells.clone() * expr);
+            exprs.into_iter().chain(square_exprs).collect::<Vec<_>>()
         });
 
+        let packed_rounds = layout.partial_rounds_per_row - 1;
+        let packed_state: Vec<Column<Advice>> =
+            (0..packed_rounds).map(|_| meta.advice_column()).collect();
+        let packed_round_constants: Vec<[Column<Fixed>; WIDTH]> = (0..packed_rounds)
+            .map(|_| [(); WIDTH].map(|_| meta.fixed_column()))
+            .collect();
+        let packed_round_selector = (packed_rounds > 0).then(|| meta.selector());
+
+        if let Some(packed_round_selector) = packed_round_selector {
+            meta.create_gate("packed_partial_rounds", |v_cells| {
+                let mut linear = LinearState::<F, WIDTH>::new();
+                let mut exprs = Vec::new();
+                for packed in 0..=packed_rounds {
+                    let constant_columns = match packed {
+                        0 => {
+                            for i in 0..WIDTH {
+                                linear.add(i, v_cells.query_advice(state[i], Rotation::cur()));
+                            }
+                            &round_constants
+                        }
+                        _ => {
+                            let column = packed_state[packed - 1];
+                            let lane_zero = v_cells.query_advice(column, Rotation::cur());
+                            exprs.push(linear.expr(0) - lane_zero.clone());
+                            linear.set(0, lane_zero);
+                            &packed_round_constants[packed - 1]
+                        }
+                    };
+                    for i in 0..WIDTH {
+                        let round_constant =
+                            v_cells.query_fixed(constant_columns[i], Rotation::cur());
+                        linear.add(i, round_constant);
+                    }
+                    let square = sbox_squares.get(packed);
+                    let sbox = Self::sbox_expr(v_cells, square, linear.expr(0), &mut exprs);
+                    linear.set(0, sbox);
+                    linear.apply_mds(&mds);
+                }
+
+                // It should be equal to the state in next row
+                for i in 0..WIDTH {
+                    let next_state = v_cells.query_advice(state[i], Rotation::next());
+                    exprs.push(linear.expr(i) - next_state);
+                }
+
+                let s_cells = v_cells.query_selector(packed_round_selector);
+                exprs
+                    .into_iter()
+                    .map(|expr| s_cells.clone() * expr)
+                    .collect::<Vec<_>>()
+            });
+        }
+
         PoseidonConfig {
             state,
             round_constants,
-            mds,
             full_round_selector,
             partial_round_selector,
+            packed_state,
+            packed_round_constants,
+            packed_round_selector,
+            sbox_squares,
         }
     }
 
-    fn permute(
+    pub fn permute(
         &self,
         config: &PoseidonConfig<WIDTH>,
         mut layouter: impl Layouter<F>,
@@ -373,43 +589,69 @@ where
 
 #[cfg(test)]
 mod test {
-    use super::params::{bn254_5x5::Params5x5Bn254, hex_to_field};
+    use super::params::{
+        bn254_2x5::Params2x5Bn254, bn254_3x5::Params3x5Bn254, bn254_4x5::Params4x5Bn254,
+        bn254_5x5::Params5x5Bn254, bn254_6x5::Params6x5Bn254, hex_to_field,
+    };
     use super::*;
     use halo2_proofs::{
+        arithmetic::Field,
         circuit::{Layouter, SimpleFloorPlanner},
         dev::MockProver,
         pairing::bn256::Fr,
         plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
     };
 
-    type TestPoseidonChip = PoseidonChip<Fr, 5, Params5x5Bn254>;
+    type PoseidonTester5x5<const ROUNDS_PER_ROW: usize, const SQUARES: bool> =
+        PoseidonTester<5, Params5x5Bn254, ROUNDS_PER_ROW, SQUARES>;
 
     #[derive(Clone)]
-    struct PoseidonTesterConfig {
-        poseidon_config: PoseidonConfig<5>,
+    struct PoseidonTesterConfig<const WIDTH: usize> {
+        poseidon_config: PoseidonConfig<WIDTH>,
         results: Column<Instance>,
     }
 
-    struct PoseidonTester {
-        inputs: [Option<Fr>; 5],
+    /// Computes `ROUNDS_PER_ROW` partial rounds in a row, witnessing the
+    /// squares of the S-box inputs if `SQUARES` is set.
+    struct PoseidonTester<const WIDTH: usize, P, const ROUNDS_PER_ROW: usize, const SQUARES: bool>
+    where
+        P: RoundParams<Fr, WIDTH> + PolySbox,
+    {
+        inputs: [Option<Fr>; WIDTH],
+        _params: PhantomData<P>,
     }
 
-    impl PoseidonTester {
-        fn new(inputs: [Option<Fr>; 5]) -> Self {
-            Self { inputs }
+    impl<const WIDTH: usize, P, const ROUNDS_PER_ROW: usize, const SQUARES: bool>
+        PoseidonTester<WIDTH, P, ROUNDS_PER_ROW, SQUARES>
+    where
+        P: RoundParams<Fr, WIDTH> + PolySbox,
+    {
+        fn new(inputs: [Option<Fr>; WIDTH]) -> Self {
+            Self {
+                inputs,
+                _params: PhantomData,
+            }
         }
     }
 
-    impl Circuit<Fr> for PoseidonTester {
-        type Config = PoseidonTesterConfig;
+    impl<const WIDTH: usize, P, const ROUNDS_PER_ROW: usize, const SQUARES: bool> Circuit<Fr>
+        for PoseidonTester<WIDTH, P, ROUNDS_PER_ROW, SQUARES>
+    where
+        P: RoundParams<Fr, WIDTH> + PolySbox,
+    {
+        type Config = PoseidonTesterConfig<WIDTH>;
         type FloorPlanner = SimpleFloorPlanner;
 
         fn without_witnesses(&self) -> Self {
-            Self { inputs: [None; 5] }
+            Self::new([None; WIDTH])
         }
 
         fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
-            let poseidon_config = TestPoseidonChip::configure(meta);
+            let layout = PoseidonLayout {
+                partial_rounds_per_row: ROUNDS_PER_ROW,
+                square_sbox_inputs: SQUARES,
+            };
+            let poseidon_config = PoseidonChip::<_, WIDTH, P>::configure_with_layout(meta, layout);
             let results = meta.instance_column();
 
             meta.enable_equality(results);
@@ -428,7 +670,7 @@ mod test {
             let init_state = layouter.assign_region(
                 || "load_state",
                 |mut region: Region<'_, Fr>| {
-                    TestPoseidonChip::load_state(
+                    PoseidonChip::<_, WIDTH, P>::load_state(
                         &config.poseidon_config,
                         &mut region,
                         0,
@@ -437,40 +679,157 @@ mod test {
                 },
             )?;
 
-            let poseidon = TestPoseidonChip::new(init_state);
+            let poseidon = PoseidonChip::<_, WIDTH, P>::new(init_state);
             let result_state =
                 poseidon.permute(&config.poseidon_config, layouter.namespace(|| "poseidon"))?;
-            for i in 0..5 {
+            for i in 0..WIDTH {
                 layouter.constrain_instance(result_state[i].cell(), config.results, i)?;
             }
             Ok(())
         }
     }
 
-    #[test]
-    fn test_poseidon_x5_5() {
-        let inputs: [Option<Fr>; 5] = [
+    fn inputs() -> [Option<Fr>; 5] {
+        [
             "0x0000000000000000000000000000000000000000000000000000000000000000",
             "0x0000000000000000000000000000000000000000000000000000000000000001",
             "0x0000000000000000000000000000000000000000000000000000000000000002",
             "0x0000000000000000000000000000000000000000000000000000000000000003",
             "0x0000000000000000000000000000000000000000000000000000000000000004",
         ]
-        .map(|n| Some(hex_to_field(n)));
+        .map(|n| Some(hex_to_field(n)))
+    }
 
-        let outputs: [Fr; 5] = [
+    fn outputs() -> [Fr; 5] {
+        [
             "0x299c867db6c1fdd79dcefa40e4510b9837e60ebb1ce0663dbaa525df65250465",
             "0x1148aaef609aa338b27dafd89bb98862d8bb2b429aceac47d86206154ffe053d",
             "0x24febb87fed7462e23f6665ff9a0111f4044c38ee1672c1ac6b0637d34f24907",
             "0x0eb08f6d809668a981c186beaf6110060707059576406b248e5d9cf6e78b3d3e",
             "0x07748bc6877c9b82c8b98666ee9d0626ec7f5be4205f79ee8528ef1c4a376fc7",
         ]
-        .map(|n| hex_to_field(n));
+        .map(|n| hex_to_field(n))
+    }
+
+    #[test]
+    fn test_poseidon_x5_5() {
+        let poseidon_tester = PoseidonTester5x5::<1, false>::new(inputs());
+
+        let k = 9;
+        let prover = MockProver::run(k, &poseidon_tester, vec![outputs().to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_poseidon_x5_5_packed_rounds() {
+        // 30 rows of partial rounds.
+        let poseidon_tester = PoseidonTester5x5::<2, false>::new(inputs());
+        let k = 6;
+        let prover = MockProver::run(k, &poseidon_tester, vec![outputs().to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // 8 rows of 7 rounds and 4 of a single round.
+        let poseidon_tester = PoseidonTester5x5::<7, false>::new(inputs());
+        let k = 6;
+        let prover = MockProver::run(k, &poseidon_tester, vec![outputs().to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
 
-        let poseidon_tester = PoseidonTester::new(inputs);
+    #[test]
+    fn test_poseidon_x5_5_packed_rounds_wrong_lane() {
+        let mut outputs = outputs();
+        outputs[1] += Fr::one();
+
+        let poseidon_tester = PoseidonTester5x5::<3, false>::new(inputs());
+        let k = 6;
+        let prover = MockProver::run(k, &poseidon_tester, vec![outputs.to_vec()]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_poseidon_x5_5_squared_sbox_inputs() {
+        let poseidon_tester = PoseidonTester5x5::<1, true>::new(inputs());
+        let k = 9;
+        let prover = MockProver::run(k, &poseidon_tester, vec![outputs().to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // 12 rows of 5 rounds, each with its own square.
+        let poseidon_tester = PoseidonTester5x5::<5, true>::new(inputs());
+        let k = 6;
+        let prover = MockProver::run(k, &poseidon_tester, vec![outputs().to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_poseidon_x5_5_squared_sbox_inputs_degree() {
+        let degree = |square_sbox_inputs| {
+            let mut meta = ConstraintSystem::<Fr>::default();
+            let layout = PoseidonLayout {
+                partial_rounds_per_row: 3,
+                square_sbox_inputs,
+            };
+            PoseidonChip::<_, 5, Params5x5Bn254>::configure_with_layout(&mut meta, layout);
+            meta.degree()
+        };
+        // `x^5` and the selector, against `square^2 * x` and the selector.
+        assert_eq!(degree(false), 6);
+        assert_eq!(degree(true), 4);
+    }
 
+    /// Checks the chip on `[0, 1, ..., WIDTH - 1]` against the circomlib
+    /// state, the same as the native known-answer tests.
+    fn check_known_answer<const WIDTH: usize, P>(outputs: [&str; WIDTH])
+    where
+        P: RoundParams<Fr, WIDTH> + PolySbox,
+    {
+        let mut inputs = [None; WIDTH];
+        for (i, input) in inputs.iter_mut().enumerate() {
+            *input = Some(Fr::from(i as u64));
+        }
+        let outputs = outputs.map(|n| hex_to_field(n));
+
+        let poseidon_tester = PoseidonTester::<WIDTH, P, 1, false>::new(inputs);
         let k = 9;
         let prover = MockProver::run(k, &poseidon_tester, vec![outputs.to_vec()]).unwrap();
         assert_eq!(prover.verify(), Ok(()));
     }
+
+    #[test]
+    fn test_poseidon_x5_2() {
+        check_known_answer::<2, Params2x5Bn254>([
+            "0x29176100eaa962bdc1fe6c654d6a3c130e96a4d1168b33848b897dc502820133",
+            "0x112a4f9241e384b0ede4655e6d2bbf7ebd9595775de9e7536df87cd487852fc4",
+        ]);
+    }
+
+    #[test]
+    fn test_poseidon_x5_3() {
+        check_known_answer::<3, Params3x5Bn254>([
+            "0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a",
+            "0x0fca49b798923ab0239de1c9e7a4a9a2210312b6a2f616d18b5a87f9b628ae29",
+            "0x0e7ae82e40091e63cbd4f16a6d16310b3729d4b6e138fcf54110e2867045a30c",
+        ]);
+    }
+
+    #[test]
+    fn test_poseidon_x5_4() {
+        check_known_answer::<4, Params4x5Bn254>([
+            "0x0e7732d89e6939c0ff03d5e58dab6302f3230e269dc5b968f725df34ab36d732",
+            "0x07b0b86b41ec7fdfe6c17ee6ccdddce4e47e748e493e542f9a435b0dde022a0d",
+            "0x04362e50fcc8be421898d47ace20eab18b0a6efab0e12ade49f2df609fec4209",
+            "0x1a779bd9781d3a8354eae5ed74e7fa44fa0e458e45a1407524bddf3b9f2bf2d7",
+        ]);
+    }
+
+    #[test]
+    fn test_poseidon_x5_6() {
+        check_known_answer::<6, Params6x5Bn254>([
+            "0x0dab9449e4a1398a15224c0b15a49d598b2174d305a316c918125f8feeb123c0",
+            "0x05cf88c465d1f87a273fed9f778b64367d3b32e45f274c03b4f441940f6d2db9",
+            "0x2c6c70449c91e046669977a3bf21021976db909b35843833babfbcba4937a2b3",
+            "0x2cabceb63c5f917cc45d3fcbad6733b8d42e2a2a12e1aeb4e3eb8fb6f3d2ca40",
+            "0x21ea1279294dbc9a32bc13827d052ce939d5397b4cb7b19e2ead087f41cfdfd7",
+            "0x208adf8d7f4ac061f00db710aef42f3b2f13176de26674b0a5f4436b883db6bc",
+        ]);
+    }
 }
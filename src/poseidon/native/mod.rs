@@ -1,6 +1,6 @@
 pub mod sponge;
 
-use super::params::RoundParams;
+use super::params::{optimized::OptimizedRounds, RoundParams};
 use halo2_proofs::arithmetic::FieldExt;
 use std::marker::PhantomData;
 
@@ -16,7 +16,7 @@ impl<F: FieldExt, const WIDTH: usize, P> Poseidon<F, WIDTH, P>
 where
     P: RoundParams<F, WIDTH>,
 {
-    fn new(inputs: [F; WIDTH]) -> Self {
+    pub fn new(inputs: [F; WIDTH]) -> Self {
         Poseidon {
             inputs,
             _params: PhantomData,
@@ -47,7 +47,7 @@ where
         new_state
     }
 
-    fn permute(&self) -> [F; WIDTH] {
+    pub fn permute(&self) -> [F; WIDTH] {
         let full_rounds = P::full_rounds();
         let half_full_rounds = full_rounds / 2;
         let partial_rounds = P::partial_rounds();
@@ -59,9 +59,8 @@ where
         let first_round_constants = &round_constants[0..first_round_end];
 
         let second_round_end = first_round_end + partial_rounds * WIDTH;
-        let second_round_constants = &round_constants[first_round_end..second_round_end];
-
         let third_round_constants = &round_constants[second_round_end..total_count];
+        let optimized = OptimizedRounds::cached::<P>();
 
         let mut state = self.inputs;
         for round in 0..half_full_rounds {
@@ -73,11 +72,11 @@ where
             state = Self::apply_mds(&state, &mds);
         }
 
+        state = Self::apply_mds(&state, &optimized.pre_mds);
         for round in 0..partial_rounds {
-            let round_consts = P::load_round_constants(round, second_round_constants);
-            state = Self::apply_round_constants(&state, &round_consts);
+            state = Self::apply_round_constants(&state, &optimized.round_constants[round]);
             state[0] = P::sbox_f(state[0]);
-            state = Self::apply_mds(&state, &mds);
+            state = optimized.sparse_mds[round].apply(&state);
         }
 
         for round in 0..half_full_rounds {
@@ -96,7 +95,11 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::poseidon::params::{bn254_5x5::Params5x5Bn254, hex_to_field};
+    use crate::poseidon::params::{
+        bn254_2x5::Params2x5Bn254, bn254_3x5::Params3x5Bn254, bn254_4x5::Params4x5Bn254,
+        bn254_5x5::Params5x5Bn254, bn254_6x5::Params6x5Bn254, hex_to_field,
+    };
+    use halo2_proofs::arithmetic::Field;
     use halo2_proofs::pairing::bn256::Fr;
 
     type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
@@ -127,4 +130,68 @@ mod test {
 
         assert_eq!(out, outputs);
     }
+
+    /// Permutes `[0, 1, ..., WIDTH - 1]`. Lane `0` of the result is the
+    /// circomlib hash of `[1, ..., WIDTH - 1]`.
+    fn permute_range<const WIDTH: usize, P>() -> [Fr; WIDTH]
+    where
+        P: RoundParams<Fr, WIDTH>,
+    {
+        let mut inputs = [Fr::zero(); WIDTH];
+        for (i, input) in inputs.iter_mut().enumerate() {
+            *input = Fr::from(i as u64);
+        }
+        Poseidon::<Fr, WIDTH, P>::new(inputs).permute()
+    }
+
+    #[test]
+    fn test_native_poseidon_2x5() {
+        let outputs: [Fr; 2] = [
+            "0x29176100eaa962bdc1fe6c654d6a3c130e96a4d1168b33848b897dc502820133",
+            "0x112a4f9241e384b0ede4655e6d2bbf7ebd9595775de9e7536df87cd487852fc4",
+        ]
+        .map(|n| hex_to_field(n));
+
+        assert_eq!(permute_range::<2, Params2x5Bn254>(), outputs);
+    }
+
+    #[test]
+    fn test_native_poseidon_3x5() {
+        let outputs: [Fr; 3] = [
+            "0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a",
+            "0x0fca49b798923ab0239de1c9e7a4a9a2210312b6a2f616d18b5a87f9b628ae29",
+            "0x0e7ae82e40091e63cbd4f16a6d16310b3729d4b6e138fcf54110e2867045a30c",
+        ]
+        .map(|n| hex_to_field(n));
+
+        assert_eq!(permute_range::<3, Params3x5Bn254>(), outputs);
+    }
+
+    #[test]
+    fn test_native_poseidon_4x5() {
+        let outputs: [Fr; 4] = [
+            "0x0e7732d89e6939c0ff03d5e58dab6302f3230e269dc5b968f725df34ab36d732",
+            "0x07b0b86b41ec7fdfe6c17ee6ccdddce4e47e748e493e542f9a435b0dde022a0d",
+            "0x04362e50fcc8be421898d47ace20eab18b0a6efab0e12ade49f2df609fec4209",
+            "0x1a779bd9781d3a8354eae5ed74e7fa44fa0e458e45a1407524bddf3b9f2bf2d7",
+        ]
+        .map(|n| hex_to_field(n));
+
+        assert_eq!(permute_range::<4, Params4x5Bn254>(), outputs);
+    }
+
+    #[test]
+    fn test_native_poseidon_6x5() {
+        let outputs: [Fr; 6] = [
+            "0x0dab9449e4a1398a15224c0b15a49d598b2174d305a316c918125f8feeb123c0",
+            "0x05cf88c465d1f87a273fed9f778b64367d3b32e45f274c03b4f441940f6d2db9",
+            "0x2c6c70449c91e046669977a3bf21021976db909b35843833babfbcba4937a2b3",
+            "0x2cabceb63c5f917cc45d3fcbad6733b8d42e2a2a12e1aeb4e3eb8fb6f3d2ca40",
+            "0x21ea1279294dbc9a32bc13827d052ce939d5397b4cb7b19e2ead087f41cfdfd7",
+            "0x208adf8d7f4ac061f00db710aef42f3b2f13176de26674b0a5f4436b883db6bc",
+        ]
+        .map(|n| hex_to_field(n));
+
+        assert_eq!(permute_range::<6, Params6x5Bn254>(), outputs);
+    }
 }
@@ -1,50 +1,201 @@
-use crate::poseidon::{native::Poseidon, RoundParams};
+use crate::hasher::Hasher;
+use crate::poseidon::native::Poseidon;
 use halo2_proofs::arithmetic::FieldExt;
 use std::marker::PhantomData;
 
-pub struct PoseidonSponge<F: FieldExt, const WIDTH: usize, P>
+/// Capacity element a sponge for `domain` starts from. This is the Poseidon
+/// paper's tag for variable-input-length hashing, `2^64`, offset by an
+/// application domain so different uses of the sponge never share a state.
+pub fn domain_tag<F: FieldExt>(domain: u64) -> F {
+    F::from_u128((1u128 << 64) + domain as u128)
+}
+
+/// Duplex sponge over the permutation of `H`. Lane `0` of the state is the
+/// capacity and holds the domain tag, the other `WIDTH - 1` lanes are the rate.
+/// Inputs absorbed between two squeezes are padded with a single `1` and then
+/// zeros up to a multiple of the rate, so messages of different lengths never
+/// absorb to the same state. Squeezing reads the rate lanes in order and
+/// permutes once they are used up.
+pub struct Sponge<F: FieldExt, const WIDTH: usize, H>
 where
-    P: RoundParams<F, WIDTH>,
+    H: Hasher<F, WIDTH>,
 {
+    state: [F; WIDTH],
     inputs: Vec<F>,
-    _params: PhantomData<P>,
+    /// Whether inputs were added since the last squeeze.
+    absorbing: bool,
+    /// Number of rate lanes of the state already squeezed.
+    squeezed: usize,
+    _hasher: PhantomData<H>,
 }
 
-impl<F: FieldExt, const WIDTH: usize, P> PoseidonSponge<F, WIDTH, P>
+/// Sponge over the Poseidon permutation with the parameters `P`.
+pub type PoseidonSponge<F, const WIDTH: usize, P> = Sponge<F, WIDTH, Poseidon<F, WIDTH, P>>;
+
+impl<F: FieldExt, const WIDTH: usize, H> Sponge<F, WIDTH, H>
 where
-    P: RoundParams<F, WIDTH>,
+    H: Hasher<F, WIDTH>,
 {
+    pub const RATE: usize = WIDTH - 1;
+
     pub fn new() -> Self {
+        Self::with_domain(0)
+    }
+
+    pub fn with_domain(domain: u64) -> Self {
+        assert!(WIDTH > 1);
+        let mut state = [F::zero(); WIDTH];
+        state[0] = domain_tag(domain);
         Self {
+            state,
             inputs: Vec::new(),
-            _params: PhantomData,
+            absorbing: true,
+            squeezed: 0,
+            _hasher: PhantomData,
         }
     }
 
     pub fn update(&mut self, inputs: &[F]) {
         self.inputs.extend_from_slice(inputs);
+        self.absorbing = true;
+    }
+
+    /// `inputs` followed by the `10*` padding, a multiple of `RATE` long.
+    pub fn pad(inputs: &[F]) -> Vec<F> {
+        let mut padded = inputs.to_vec();
+        padded.push(F::one());
+        while padded.len() % Self::RATE != 0 {
+            padded.push(F::zero());
+        }
+        padded
     }
 
-	pub fn load_state(chunk: &[F]) -> [F; WIDTH] {
-		assert!(chunk.len() <= WIDTH);
-		let mut fixed_chunk = [F::zero(); WIDTH];
-		fixed_chunk[..chunk.len()].copy_from_slice(chunk);
-		fixed_chunk
-	}
+    fn permute(&mut self) {
+        self.state = H::permute(self.state);
+    }
 
     pub fn squeeze(&mut self) -> F {
-        assert!(self.inputs.len() > 0);
+        if self.absorbing {
+            for chunk in Self::pad(&self.inputs).chunks(Self::RATE) {
+                for (i, item) in chunk.iter().enumerate() {
+                    self.state[i + 1] += *item;
+                }
+                self.permute();
+            }
+            self.inputs.clear();
+            self.absorbing = false;
+            self.squeezed = 0;
+        } else if self.squeezed == Self::RATE {
+            self.permute();
+            self.squeezed = 0;
+        }
 
-        let mut state = [F::zero(); WIDTH];
+        self.squeezed += 1;
+        self.state[self.squeezed]
+    }
 
-        for chunk in self.inputs.chunks(WIDTH) {
-			let loaded_state = Self::load_state(chunk);
-			let input = loaded_state.zip(state).map(|(lhs, rhs)| lhs + rhs);
+    pub fn squeeze_n(&mut self, n: usize) -> Vec<F> {
+        (0..n).map(|_| self.squeeze()).collect()
+    }
+}
 
-            let pos = Poseidon::<_, WIDTH, P>::new(input);
-            state = pos.permute();
-        }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::params::bn254_5x5::Params5x5Bn254;
+    use crate::rescue::{native::RescuePrime, params as rescue_params};
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    type TestPoseidonSponge = PoseidonSponge<Fr, 5, Params5x5Bn254>;
+
+    fn hash(domain: u64, inputs: &[Fr]) -> Fr {
+        let mut sponge = TestPoseidonSponge::with_domain(domain);
+        sponge.update(inputs);
+        sponge.squeeze()
+    }
+
+    #[test]
+    fn should_separate_trailing_zeros() {
+        let a = Fr::from(7);
+        assert_ne!(hash(0, &[a]), hash(0, &[a, Fr::zero()]));
+        // A full rate block gets a block of padding on its own.
+        let block = [a, a, a, a];
+        assert_ne!(hash(0, &block), hash(0, &[a, a, a, a, Fr::zero()]));
+        assert_ne!(hash(0, &[]), hash(0, &[Fr::zero()]));
+    }
+
+    #[test]
+    fn should_separate_domains() {
+        let inputs = [Fr::from(1), Fr::from(2)];
+        assert_ne!(hash(0, &inputs), hash(1, &inputs));
+    }
+
+    #[test]
+    fn should_squeeze_rate_lanes_then_permute() {
+        let inputs = [Fr::from(1), Fr::from(2), Fr::from(3)];
+        let mut sponge = TestPoseidonSponge::new();
+        sponge.update(&inputs);
+        let outputs = sponge.squeeze_n(6);
+
+        let state = [domain_tag(0), inputs[0], inputs[1], inputs[2], Fr::one()];
+        let state = Poseidon::<Fr, 5, Params5x5Bn254>::new(state).permute();
+        assert_eq!(outputs[..4], state[1..]);
+        let state = Poseidon::<Fr, 5, Params5x5Bn254>::new(state).permute();
+        assert_eq!(outputs[4..], state[1..3]);
+    }
+
+    #[test]
+    fn should_duplex() {
+        let mut sponge = TestPoseidonSponge::new();
+        sponge.update(&[Fr::from(1)]);
+        let first = sponge.squeeze();
+        sponge.update(&[Fr::from(2)]);
+        let second = sponge.squeeze();
+
+        // Absorbing after a squeeze continues from the squeezed state.
+        let mut state = [
+            domain_tag(0),
+            Fr::from(1),
+            Fr::one(),
+            Fr::zero(),
+            Fr::zero(),
+        ];
+        state = Poseidon::<Fr, 5, Params5x5Bn254>::new(state).permute();
+        assert_eq!(first, state[1]);
+        state[1] += Fr::from(2);
+        state[2] += Fr::one();
+        state = Poseidon::<Fr, 5, Params5x5Bn254>::new(state).permute();
+        assert_eq!(second, state[1]);
+
+        // An empty absorb still separates two squeezes.
+        let mut sponge = TestPoseidonSponge::new();
+        sponge.update(&[Fr::from(1)]);
+        sponge.squeeze();
+        let next = sponge.squeeze();
+        sponge.update(&[]);
+        assert_ne!(next, sponge.squeeze());
+    }
+
+    #[test]
+    fn should_match_single_permutation() {
+        let inputs = [Fr::from(1), Fr::from(2), Fr::from(3)];
+        let state = [domain_tag(0), inputs[0], inputs[1], inputs[2], Fr::one()];
+        let expected = Poseidon::<Fr, 5, Params5x5Bn254>::new(state).permute()[1];
+        assert_eq!(hash(0, &inputs), expected);
+    }
+
+    #[test]
+    fn should_switch_to_rescue_prime() {
+        type TestRescuePrime = RescuePrime<Fr, 5, rescue_params::Params5x5Bn254>;
+
+        let inputs = [Fr::from(1), Fr::from(2), Fr::from(3)];
+        let mut sponge = Sponge::<Fr, 5, TestRescuePrime>::new();
+        sponge.update(&inputs);
 
-        state[0]
+        let state = [domain_tag(0), inputs[0], inputs[1], inputs[2], Fr::one()];
+        let expected = TestRescuePrime::new(state).permute()[1];
+        assert_eq!(sponge.squeeze(), expected);
+        assert_ne!(expected, hash(0, &inputs));
     }
 }
@@ -1,32 +1,35 @@
+use super::sbox::Pow5;
 use super::*;
 use maingate::halo2::pairing::bn256::Fr;
 
 pub struct Params10x5Bn254;
 
 impl Sbox for Params10x5Bn254 {
-    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
-        let exp2 = exp.clone() * exp.clone();
-        let exp4 = exp2.clone() * exp2;
-        let exp5 = exp4 * exp;
-        exp5
-    }
+    const SBOX: SboxType = Pow5::SBOX;
 
     fn sbox_asgn<F: FieldExt>(
         main_gate: &MainGate<F>,
         ctx: &mut RegionCtx<'_, '_, F>,
         exp: &AssignedValue<F>,
     ) -> Result<AssignedValue<F>, Error> {
-        let exp2 = main_gate.mul(ctx, exp, exp)?;
-        let exp4 = main_gate.mul(ctx, &exp2, &exp2)?;
-        let exp5 = main_gate.mul(ctx, &exp4, &exp)?;
-        Ok(exp5)
+        Pow5::sbox_asgn(main_gate, ctx, exp)
     }
 
     fn sbox_f<F: FieldExt>(f: F) -> F {
-        let f2 = f.clone() * f.clone();
-        let f4 = f2.clone() * f2;
-        let f5 = f4 * f;
-        f5
+        Pow5::sbox_f(f)
+    }
+}
+
+impl PolySbox for Params10x5Bn254 {
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+        Pow5::sbox_expr(exp)
+    }
+
+    fn sbox_expr_from_square<F: FieldExt>(
+        exp: Expression<F>,
+        square: Expression<F>,
+    ) -> Expression<F> {
+        Pow5::sbox_expr_from_square(exp, square)
     }
 }
 
@@ -39,7 +42,7 @@ impl RoundParams<Fr, 10> for Params10x5Bn254 {
         8
     }
 
-    fn round_constants_raw() -> Vec<&'static str> {
+    fn round_constants() -> Vec<Fr> {
         [
             "0x0e1962c232fd0a6bb54ad8962a82b9838cfef19d290a55fc49d6debd061cd2f6",
             "0x01e1f45cff3e74168accd0a94536fca6317128953eb47d8d2d79b847b355cf51",
@@ -722,10 +725,12 @@ impl RoundParams<Fr, 10> for Params10x5Bn254 {
             "0x1af9d63ace6db7a8ae20a04b776fa39de34b795394231214317f036e5954eea4",
             "0x1b3a2d08d1c3763b7678b9d9de1fee10cda80937fd7d74f27d6e550386f5443e",
         ]
-        .to_vec()
+        .iter()
+        .map(|x| hex_to_field(x))
+        .collect()
     }
 
-    fn mds_raw() -> [[&'static str; 10]; 10] {
+    fn mds() -> [[Fr; 10]; 10] {
         [
             [
                 "0x0b1e797eee3f15b88ce3a1fafec88da1d6329ac3c34712db570de886cf569098",
@@ -848,5 +853,6 @@ impl RoundParams<Fr, 10> for Params10x5Bn254 {
                 "0x15eeb3e0541bcf010da0c47adb12f6df540b974d439c93e19ea4d02f9975d446",
             ],
         ]
+        .map(|row| row.map(|item| hex_to_field(item)))
     }
 }
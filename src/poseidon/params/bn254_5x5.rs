@@ -1,32 +1,35 @@
+use super::sbox::Pow5;
 use super::*;
 use maingate::halo2::pairing::bn256::Fr;
 
 pub struct Params5x5Bn254;
 
 impl Sbox for Params5x5Bn254 {
-    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
-        let exp2 = exp.clone() * exp.clone();
-        let exp4 = exp2.clone() * exp2;
-        let exp5 = exp4 * exp;
-        exp5
-    }
+    const SBOX: SboxType = Pow5::SBOX;
 
     fn sbox_asgn<F: FieldExt>(
         main_gate: &MainGate<F>,
         ctx: &mut RegionCtx<'_, '_, F>,
         exp: &AssignedValue<F>,
     ) -> Result<AssignedValue<F>, Error> {
-        let exp2 = main_gate.mul(ctx, exp, exp)?;
-        let exp4 = main_gate.mul(ctx, &exp2, &exp2)?;
-        let exp5 = main_gate.mul(ctx, &exp4, &exp)?;
-        Ok(exp5)
+        Pow5::sbox_asgn(main_gate, ctx, exp)
     }
 
     fn sbox_f<F: FieldExt>(f: F) -> F {
-        let f2 = f.clone() * f.clone();
-        let f4 = f2.clone() * f2;
-        let f5 = f4 * f;
-        f5
+        Pow5::sbox_f(f)
+    }
+}
+
+impl PolySbox for Params5x5Bn254 {
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+        Pow5::sbox_expr(exp)
+    }
+
+    fn sbox_expr_from_square<F: FieldExt>(
+        exp: Expression<F>,
+        square: Expression<F>,
+    ) -> Expression<F> {
+        Pow5::sbox_expr_from_square(exp, square)
     }
 }
 
@@ -39,7 +42,7 @@ impl RoundParams<Fr, 5> for Params5x5Bn254 {
         8
     }
 
-    fn round_constants_raw() -> Vec<&'static str> {
+    fn round_constants() -> Vec<Fr> {
         [
             "0x0eb544fee2815dda7f53e29ccac98ed7d889bb4ebd47c3864f3c2bd81a6da891",
             "0x0554d736315b8662f02fdba7dd737fbca197aeb12ea64713ba733f28475128cb",
@@ -382,10 +385,12 @@ impl RoundParams<Fr, 5> for Params5x5Bn254 {
             "0x198d07192db4fac2a82a4a79839d6a2b97c4dd4d37b4e8f3b53009f79b34e6a4",
             "0x29eb1de42a3ad381b23b4131426897a32709b29d53bb946dfd15784d1f63e572",
         ]
-        .to_vec()
+        .iter()
+        .map(|x| hex_to_field(x))
+        .collect()
     }
 
-    fn mds_raw() -> [[&'static str; 5]; 5] {
+    fn mds() -> [[Fr; 5]; 5] {
         [
             [
                 "0x251e7fdf99591080080b0af133b9e4369f22e57ace3cd7f64fc6fdbcf38d7da1",
@@ -423,5 +428,6 @@ impl RoundParams<Fr, 5> for Params5x5Bn254 {
                 "0x14074bb14c982c81c9ad171e4f35fe49b39c4a7a72dbb6d9c98d803bfed65e64",
             ],
         ]
+        .map(|row| row.map(|item| hex_to_field(item)))
     }
 }
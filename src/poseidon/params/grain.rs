@@ -0,0 +1,411 @@
+use super::security::infinite_subspace_trail;
+use super::{PolySbox, RoundParams, Sbox};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::{Error, Expression};
+use maingate::{AssignedValue, MainGate, RegionCtx};
+use std::marker::PhantomData;
+
+/// Number of bits in the Grain LFSR state.
+const STATE: usize = 80;
+
+/// S-box families known to the reference parameter generation. The tag is
+/// part of the Grain seed, so different families never share constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SboxType {
+    /// `x^alpha`
+    Pow(u64),
+    /// `x^-1`
+    Inverse,
+}
+
+impl SboxType {
+    fn tag(&self) -> u16 {
+        match self {
+            SboxType::Pow(_) => 0,
+            SboxType::Inverse => 1,
+        }
+    }
+}
+
+/// The Grain LFSR of the Poseidon reference implementation
+/// (`generate_parameters_grain.sage`). Seeded with the field, the S-box and
+/// the round numbers, it yields the round constants and the MDS matrix.
+pub struct Grain<F: FieldExt> {
+    state: Vec<bool>,
+    _field: PhantomData<F>,
+}
+
+impl<F: FieldExt> Grain<F> {
+    pub fn new(sbox: SboxType, width: usize, full_rounds: usize, partial_rounds: usize) -> Self {
+        let mut state = Vec::with_capacity(STATE);
+        let mut push_bits = |value: u16, len: usize| {
+            for i in (0..len).rev() {
+                state.push((value >> i) & 1 == 1);
+            }
+        };
+        // Prime field.
+        push_bits(1, 2);
+        push_bits(sbox.tag(), 4);
+        push_bits(F::NUM_BITS as u16, 12);
+        push_bits(width as u16, 12);
+        push_bits(full_rounds as u16, 10);
+        push_bits(partial_rounds as u16, 10);
+        state.resize(STATE, true);
+
+        let mut grain = Self {
+            state,
+            _field: PhantomData,
+        };
+        // The first 160 bits are discarded.
+        for _ in 0..160 {
+            grain.step();
+        }
+        grain
+    }
+
+    fn step(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.remove(0);
+        self.state.push(bit);
+        bit
+    }
+
+    /// Output bits are taken in pairs: the second bit is kept when the first
+    /// one is set, and the pair is dropped otherwise.
+    pub fn next_bit(&mut self) -> bool {
+        loop {
+            let keep = self.step();
+            let bit = self.step();
+            if keep {
+                return bit;
+            }
+        }
+    }
+
+    /// Little-endian bytes of the next `NUM_BITS` output bits, read most
+    /// significant bit first as the reference implementation does.
+    fn next_bytes(&mut self, bytes: &mut [u8]) {
+        let num_bits = F::NUM_BITS as usize;
+        for i in (0..num_bits).rev() {
+            if self.next_bit() {
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+        }
+    }
+
+    /// Next field element, skipping outputs that are not below the modulus.
+    pub fn next_field_element(&mut self) -> F {
+        loop {
+            let mut repr = F::Repr::default();
+            self.next_bytes(repr.as_mut());
+            if let Some(f) = Option::<F>::from(F::from_repr(repr)) {
+                return f;
+            }
+        }
+    }
+
+    /// Next field element, reducing the output modulo the field order. Used
+    /// for the MDS matrix, which the reference implementation does not reject.
+    pub fn next_field_element_without_rejection(&mut self) -> F {
+        let mut bytes = [0u8; 64];
+        self.next_bytes(&mut bytes);
+        F::from_bytes_wide(&bytes)
+    }
+
+    pub fn round_constants(&mut self, count: usize) -> Vec<F> {
+        (0..count).map(|_| self.next_field_element()).collect()
+    }
+
+    /// Cauchy matrix `1 / (x_i + y_j)` over `2 * WIDTH` distinct elements.
+    /// Must be drawn after the round constants. Like the reference, matrices
+    /// with an infinitely long subspace trail are dropped and re-sampled.
+    pub fn mds<const WIDTH: usize>(&mut self) -> [[F; WIDTH]; WIDTH] {
+        'sample: loop {
+            let elements: Vec<F> = (0..2 * WIDTH)
+                .map(|_| self.next_field_element_without_rejection())
+                .collect();
+            for (i, element) in elements.iter().enumerate() {
+                if elements[i + 1..].contains(element) {
+                    continue 'sample;
+                }
+            }
+
+            let (xs, ys) = elements.split_at(WIDTH);
+            let mut mds = [[F::zero(); WIDTH]; WIDTH];
+            for i in 0..WIDTH {
+                for j in 0..WIDTH {
+                    match Option::<F>::from((xs[i] + ys[j]).invert()) {
+                        Some(entry) => mds[i][j] = entry,
+                        None => continue 'sample,
+                    }
+                }
+            }
+            if infinite_subspace_trail(&mds) {
+                continue 'sample;
+            }
+            return mds;
+        }
+    }
+}
+
+/// `log2` of the field modulus.
+fn log2_modulus<F: FieldExt>() -> f64 {
+    let repr = (-F::one()).to_repr();
+    let bytes = repr.as_ref();
+    let top = bytes.iter().rposition(|b| *b != 0).unwrap_or(0);
+    // The eight most significant non-zero bytes are plenty for an `f64`.
+    let start = top.saturating_sub(7);
+    let mut head = 0u64;
+    for b in bytes[start..=top].iter().rev() {
+        head = (head << 8) | *b as u64;
+    }
+    (head as f64).log2() + (start * 8) as f64
+}
+
+/// Least number of full rounds resisting each attack of the Poseidon paper,
+/// for a given number of partial rounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FullRoundBounds {
+    pub statistical: usize,
+    pub interpolation: usize,
+    pub groebner: usize,
+}
+
+impl FullRoundBounds {
+    pub fn min_full_rounds(&self) -> usize {
+        self.statistical.max(self.interpolation).max(self.groebner)
+    }
+}
+
+pub fn full_round_bounds<F: FieldExt>(
+    width: usize,
+    partial_rounds: usize,
+    alpha: u64,
+    security: usize,
+) -> FullRoundBounds {
+    let t = width as f64;
+    let r_p = partial_rounds as f64;
+    let m = security as f64;
+    let n = F::NUM_BITS as f64;
+    let log_p = log2_modulus::<F>();
+    let log_alpha = |x: f64| x.ln() / (alpha as f64).ln();
+    let rounds = |x: f64| x.ceil().max(0.0) as usize;
+
+    let statistical = if m <= (log_p - (alpha as f64 - 1.0) / 2.0).floor() * (t + 1.0) {
+        6
+    } else {
+        10
+    };
+    let interpolation = 1.0 + (log_alpha(2.0) * m.min(n)).ceil() + log_alpha(t).ceil() - r_p;
+    let groebner_1 = 1.0 + log_alpha(2.0) * (m / 3.0).min(log_p / 2.0) - r_p;
+    let groebner_2 = t - 1.0 + log_alpha(2.0) * (m / (t + 1.0)).min(log_p / 2.0) - r_p;
+    let groebner_3 = (t - 2.0 + m / (2.0 * (alpha as f64).log2()) - r_p) / (t - 1.0);
+
+    FullRoundBounds {
+        statistical,
+        interpolation: rounds(interpolation),
+        groebner: rounds(groebner_1)
+            .max(rounds(groebner_2))
+            .max(rounds(groebner_3)),
+    }
+}
+
+/// Full and partial round numbers for an `x^alpha` S-box at `security` bits,
+/// the cheapest secure pair in S-boxes with the reference security margin of
+/// two more full rounds and 7.5% more partial rounds.
+pub fn round_numbers<F: FieldExt>(width: usize, alpha: u64, security: usize) -> (usize, usize) {
+    let mut best = (0, 0);
+    let mut min_cost = usize::MAX;
+    for partial_rounds in 1..500 {
+        // Rounds are searched the way the reference script does, the margin
+        // carries over to the following full round numbers.
+        let mut partial_rounds = partial_rounds;
+        for full_rounds in (4..100).step_by(2) {
+            let bounds = full_round_bounds::<F>(width, partial_rounds, alpha, security);
+            if full_rounds < bounds.min_full_rounds() {
+                continue;
+            }
+            let full_rounds = full_rounds + 2;
+            partial_rounds = (partial_rounds as f64 * 1.075).ceil() as usize;
+
+            let cost = full_rounds * width + partial_rounds;
+            if cost < min_cost || (cost == min_cost && full_rounds < best.0) {
+                best = (full_rounds, partial_rounds);
+                min_cost = cost;
+            }
+        }
+    }
+    best
+}
+
+/// Round numbers of the circomlib tables the `bn254_*` parameters come from.
+/// circomlib takes `round_numbers` and rounds the partial rounds up to a
+/// multiple of the width, so at widths 3 and 5 the tables hold 57 and 60
+/// partial rounds where the reference script gives 56.
+pub fn circomlib_round_numbers<F: FieldExt>(
+    width: usize,
+    alpha: u64,
+    security: usize,
+) -> (usize, usize) {
+    let (full_rounds, partial_rounds) = round_numbers::<F>(width, alpha, security);
+    (full_rounds, (partial_rounds + width - 1) / width * width)
+}
+
+/// `RoundParams` generated with Grain for any field and width, using the
+/// S-box of `S`.
+pub struct GrainParams<S: Sbox, const FULL_ROUNDS: usize, const PARTIAL_ROUNDS: usize>(
+    PhantomData<S>,
+);
+
+impl<S: Sbox, const FULL_ROUNDS: usize, const PARTIAL_ROUNDS: usize> Sbox
+    for GrainParams<S, FULL_ROUNDS, PARTIAL_ROUNDS>
+{
+    const SBOX: SboxType = S::SBOX;
+
+    fn sbox_asgn<F: FieldExt>(
+        main_gate: &MainGate<F>,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        exp: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        S::sbox_asgn(main_gate, ctx, exp)
+    }
+
+    fn sbox_f<F: FieldExt>(f: F) -> F {
+        S::sbox_f(f)
+    }
+}
+
+impl<S: PolySbox, const FULL_ROUNDS: usize, const PARTIAL_ROUNDS: usize> PolySbox
+    for GrainParams<S, FULL_ROUNDS, PARTIAL_ROUNDS>
+{
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+        S::sbox_expr(exp)
+    }
+
+    fn sbox_expr_from_square<F: FieldExt>(
+        exp: Expression<F>,
+        square: Expression<F>,
+    ) -> Expression<F> {
+        S::sbox_expr_from_square(exp, square)
+    }
+}
+
+impl<
+        F: FieldExt,
+        const WIDTH: usize,
+        S: Sbox + 'static,
+        const FULL_ROUNDS: usize,
+        const PARTIAL_ROUNDS: usize,
+    > RoundParams<F, WIDTH> for GrainParams<S, FULL_ROUNDS, PARTIAL_ROUNDS>
+{
+    fn full_rounds() -> usize {
+        FULL_ROUNDS
+    }
+
+    fn partial_rounds() -> usize {
+        PARTIAL_ROUNDS
+    }
+
+    fn round_constants() -> Vec<F> {
+        let mut grain = Grain::new(S::SBOX, WIDTH, FULL_ROUNDS, PARTIAL_ROUNDS);
+        grain.round_constants(Self::round_constants_count())
+    }
+
+    fn mds() -> [[F; WIDTH]; WIDTH] {
+        let mut grain = Grain::new(S::SBOX, WIDTH, FULL_ROUNDS, PARTIAL_ROUNDS);
+        grain.round_constants(Self::round_constants_count());
+        grain.mds()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::params::{
+        bn254_10x5::Params10x5Bn254,
+        bn254_2x5::Params2x5Bn254,
+        bn254_3x5::Params3x5Bn254,
+        bn254_4x5::Params4x5Bn254,
+        bn254_5x5::Params5x5Bn254,
+        bn254_6x5::Params6x5Bn254,
+        sbox::{Inverse, Pow5},
+    };
+    use halo2_proofs::pairing::bn256::Fr;
+
+    type Grain5x5Bn254 = GrainParams<Params5x5Bn254, 8, 60>;
+
+    type Grain10x5Bn254 = GrainParams<Params10x5Bn254, 8, 60>;
+
+    #[test]
+    fn should_match_5x5_table() {
+        assert_eq!(
+            <Grain5x5Bn254 as RoundParams<Fr, 5>>::round_constants(),
+            <Params5x5Bn254 as RoundParams<Fr, 5>>::round_constants()
+        );
+        assert_eq!(
+            <Grain5x5Bn254 as RoundParams<Fr, 5>>::mds(),
+            <Params5x5Bn254 as RoundParams<Fr, 5>>::mds()
+        );
+    }
+
+    #[test]
+    fn should_match_10x5_table() {
+        assert_eq!(
+            <Grain10x5Bn254 as RoundParams<Fr, 10>>::round_constants(),
+            <Params10x5Bn254 as RoundParams<Fr, 10>>::round_constants()
+        );
+        assert_eq!(
+            <Grain10x5Bn254 as RoundParams<Fr, 10>>::mds(),
+            <Params10x5Bn254 as RoundParams<Fr, 10>>::mds()
+        );
+    }
+
+    fn assert_matches_table<const WIDTH: usize, G, P>()
+    where
+        G: RoundParams<Fr, WIDTH>,
+        P: RoundParams<Fr, WIDTH>,
+    {
+        assert_eq!(G::round_constants(), P::round_constants());
+        assert_eq!(G::mds(), P::mds());
+    }
+
+    #[test]
+    fn should_match_small_width_tables() {
+        assert_matches_table::<2, GrainParams<Params2x5Bn254, 8, 56>, Params2x5Bn254>();
+        assert_matches_table::<3, GrainParams<Params3x5Bn254, 8, 57>, Params3x5Bn254>();
+        assert_matches_table::<4, GrainParams<Params4x5Bn254, 8, 56>, Params4x5Bn254>();
+        assert_matches_table::<6, GrainParams<Params6x5Bn254, 8, 60>, Params6x5Bn254>();
+    }
+
+    #[test]
+    fn should_seed_with_the_sbox() {
+        type Pow5Params = GrainParams<Pow5, 8, 60>;
+        type InverseParams = GrainParams<Inverse, 8, 60>;
+        assert_eq!(
+            <Pow5Params as RoundParams<Fr, 5>>::round_constants(),
+            <Params5x5Bn254 as RoundParams<Fr, 5>>::round_constants()
+        );
+        assert_ne!(
+            <InverseParams as RoundParams<Fr, 5>>::round_constants(),
+            <Pow5Params as RoundParams<Fr, 5>>::round_constants()
+        );
+
+        let mds = <InverseParams as RoundParams<Fr, 5>>::mds();
+        assert!(!infinite_subspace_trail(&mds));
+    }
+
+    #[test]
+    fn should_compute_round_numbers() {
+        assert_eq!(round_numbers::<Fr>(3, 5, 128), (8, 56));
+        assert_eq!(round_numbers::<Fr>(5, 5, 128), (8, 56));
+        assert_eq!(round_numbers::<Fr>(10, 5, 128), (8, 57));
+
+        assert_eq!(circomlib_round_numbers::<Fr>(2, 5, 128), (8, 56));
+        assert_eq!(circomlib_round_numbers::<Fr>(3, 5, 128), (8, 57));
+        assert_eq!(circomlib_round_numbers::<Fr>(4, 5, 128), (8, 56));
+        assert_eq!(circomlib_round_numbers::<Fr>(5, 5, 128), (8, 60));
+        assert_eq!(circomlib_round_numbers::<Fr>(6, 5, 128), (8, 60));
+        assert_eq!(circomlib_round_numbers::<Fr>(10, 5, 128), (8, 60));
+    }
+}
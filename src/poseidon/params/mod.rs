@@ -1,13 +1,23 @@
 pub mod bn254_10x5;
+pub mod bn254_2x5;
+pub mod bn254_3x5;
+pub mod bn254_4x5;
 pub mod bn254_5x5;
+pub mod bn254_6x5;
+pub mod grain;
+pub mod optimized;
+pub mod sbox;
+pub mod security;
 
+use grain::SboxType;
 use halo2_proofs::arithmetic::FieldExt;
 use halo2_proofs::plonk::Error;
 use halo2_proofs::plonk::Expression;
-use maingate::MainGateInstructions;
 use maingate::{AssignedValue, MainGate, RegionCtx};
 
-pub trait RoundParams<F: FieldExt, const WIDTH: usize>: Sbox {
+/// Parameters are `'static` types, so tables derived from them can be cached
+/// per parameter set.
+pub trait RoundParams<F: FieldExt, const WIDTH: usize>: Sbox + 'static {
     fn full_rounds() -> usize;
     fn partial_rounds() -> usize;
 
@@ -17,15 +27,7 @@ pub trait RoundParams<F: FieldExt, const WIDTH: usize>: Sbox {
         (partial_rounds + full_rounds) * WIDTH
     }
 
-    fn round_constants() -> Vec<F> {
-        let round_constants_raw = Self::round_constants_raw();
-        let round_constants: Vec<F> = round_constants_raw
-            .iter()
-            .map(|x| hex_to_field(x))
-            .collect();
-        assert_eq!(round_constants.len(), Self::round_constants_count());
-        round_constants
-    }
+    fn round_constants() -> Vec<F>;
 
     fn load_round_constants(round: usize, round_consts: &[F]) -> [F; WIDTH] {
         let mut result = [F::zero(); WIDTH];
@@ -35,18 +37,14 @@ pub trait RoundParams<F: FieldExt, const WIDTH: usize>: Sbox {
         result
     }
 
-    fn mds() -> [[F; WIDTH]; WIDTH] {
-        let mds_raw = Self::mds_raw();
-        let mds = mds_raw.map(|row| row.map(|item| hex_to_field(item)));
-        mds
-    }
-
-    fn round_constants_raw() -> Vec<&'static str>;
-    fn mds_raw() -> [[&'static str; WIDTH]; WIDTH];
+    fn mds() -> [[F; WIDTH]; WIDTH];
 }
 
 pub trait Sbox {
-    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F>;
+    /// Family and exponent of the S-box, which seeds the Grain parameter
+    /// generation and sets the round bounds of `security::validate`.
+    const SBOX: SboxType;
+
     fn sbox_asgn<F: FieldExt>(
         main_gate: &MainGate<F>,
         ctx: &mut RegionCtx<'_, '_, F>,
@@ -55,6 +53,40 @@ pub trait Sbox {
     fn sbox_f<F: FieldExt>(f: F) -> F;
 }
 
+/// S-boxes that are a polynomial of low degree, so the custom gates can
+/// compute them in place.
+pub trait PolySbox: Sbox {
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F>;
+
+    /// `sbox_expr` of `exp` given a witness of its square, which lowers the
+    /// degree of the power S-boxes. Defaults to `sbox_expr`.
+    fn sbox_expr_from_square<F: FieldExt>(
+        exp: Expression<F>,
+        _square: Expression<F>,
+    ) -> Expression<F> {
+        Self::sbox_expr(exp)
+    }
+}
+
+/// S-boxes whose output is witnessed and checked against the input, which
+/// covers every `PolySbox` as well as the ones with no polynomial form.
+pub trait WitnessSbox: Sbox {
+    /// Constraints that `output` is the S-box of `input`.
+    fn sbox_constraints<F: FieldExt>(
+        input: Expression<F>,
+        output: Expression<F>,
+    ) -> Vec<Expression<F>>;
+}
+
+impl<S: PolySbox> WitnessSbox for S {
+    fn sbox_constraints<F: FieldExt>(
+        input: Expression<F>,
+        output: Expression<F>,
+    ) -> Vec<Expression<F>> {
+        vec![S::sbox_expr(input) - output]
+    }
+}
+
 pub fn hex_to_field<F: FieldExt>(s: &str) -> F {
     let s = &s[2..];
     let mut bytes = hex::decode(s).expect("Invalid params");
@@ -0,0 +1,299 @@
+use super::RoundParams;
+use halo2_proofs::arithmetic::FieldExt;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// Matrix with a dense first row and first column and the identity elsewhere.
+/// `col[0]` is unused, the corner is `row[0]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SparseMds<F: FieldExt, const WIDTH: usize> {
+    pub row: [F; WIDTH],
+    pub col: [F; WIDTH],
+}
+
+impl<F: FieldExt, const WIDTH: usize> SparseMds<F, WIDTH> {
+    pub fn apply(&self, state: &[F; WIDTH]) -> [F; WIDTH] {
+        let mut new_state = *state;
+        new_state[0] = F::zero();
+        for (m, x) in self.row.iter().zip(state.iter()) {
+            new_state[0] += *m * *x;
+        }
+        for (item, m) in new_state.iter_mut().zip(self.col.iter()).skip(1) {
+            *item += *m * state[0];
+        }
+        new_state
+    }
+}
+
+/// Partial rounds of `RoundParams` in the optimized representation of the
+/// Poseidon paper (appendix B). Only lane `0` goes through the S-box, so the
+/// constants of the other lanes are moved back to the first partial round and
+/// the MDS matrix of every round is split into a sparse matrix and a dense one,
+/// which is carried back past the S-box. What is left of the dense matrices is
+/// applied once, before the partial rounds:
+///
+/// `state = pre_mds * state`, then for every round
+/// `state += round_constants[r]`, `state[0] = sbox(state[0])`,
+/// `state = sparse_mds[r] * state`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OptimizedRounds<F: FieldExt, const WIDTH: usize> {
+    /// Leaves lane `0` unchanged.
+    pub pre_mds: [[F; WIDTH]; WIDTH],
+    pub round_constants: Vec<[F; WIDTH]>,
+    pub sparse_mds: Vec<SparseMds<F, WIDTH>>,
+}
+
+/// Inverse of a square matrix by Gauss-Jordan elimination.
+fn invert<F: FieldExt>(matrix: &[Vec<F>]) -> Vec<Vec<F>> {
+    let n = matrix.len();
+    let mut rows: Vec<Vec<F>> = matrix
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut row = row.clone();
+            row.extend((0..n).map(|j| if i == j { F::one() } else { F::zero() }));
+            row
+        })
+        .collect();
+
+    for column in 0..n {
+        let pivot = (column..n)
+            .find(|i| rows[*i][column] != F::zero())
+            .expect("singular matrix");
+        rows.swap(column, pivot);
+        let inv = rows[column][column].invert().unwrap();
+        for item in rows[column].iter_mut() {
+            *item *= inv;
+        }
+        let pivot_row = rows[column].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i == column || row[column] == F::zero() {
+                continue;
+            }
+            let factor = row[column];
+            for (item, pivot_item) in row.iter_mut().zip(pivot_row.iter()) {
+                *item -= *pivot_item * factor;
+            }
+        }
+    }
+
+    rows.into_iter().map(|row| row[n..].to_vec()).collect()
+}
+
+fn mul<F: FieldExt>(a: &[Vec<F>], b: &[Vec<F>]) -> Vec<Vec<F>> {
+    a.iter()
+        .map(|row| {
+            (0..b[0].len())
+                .map(|j| {
+                    row.iter()
+                        .zip(b.iter())
+                        .fold(F::zero(), |acc, (x, b_row)| acc + *x * b_row[j])
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn mul_vec<F: FieldExt, const WIDTH: usize>(matrix: &[Vec<F>], v: &[F; WIDTH]) -> [F; WIDTH] {
+    let mut result = [F::zero(); WIDTH];
+    for (item, row) in result.iter_mut().zip(matrix.iter()) {
+        for (m, x) in row.iter().zip(v.iter()) {
+            *item += *m * *x;
+        }
+    }
+    result
+}
+
+impl<F: FieldExt, const WIDTH: usize> OptimizedRounds<F, WIDTH> {
+    pub fn new<P: RoundParams<F, WIDTH>>() -> Self {
+        let half_full_rounds = P::full_rounds() / 2;
+        let partial_rounds = P::partial_rounds();
+        let mds = P::mds();
+        let all_constants = P::round_constants();
+        let mut constants: Vec<[F; WIDTH]> = (0..partial_rounds)
+            .map(|round| P::load_round_constants(half_full_rounds + round, &all_constants))
+            .collect();
+
+        // `M * sbox(x) + c = M * (sbox(x) + M^-1 * c)`, and all but lane `0` of
+        // `M^-1 * c` can be added before the S-box. Lane `0` stays after it.
+        let mds_rows: Vec<Vec<F>> = mds.iter().map(|row| row.to_vec()).collect();
+        let mds_inv = invert(&mds_rows);
+        let mut after_sbox = vec![F::zero(); partial_rounds];
+        for round in (1..partial_rounds).rev() {
+            let moved = mul_vec(&mds_inv, &constants[round]);
+            after_sbox[round - 1] = moved[0];
+            for i in 1..WIDTH {
+                constants[round - 1][i] += moved[i];
+            }
+            constants[round] = [F::zero(); WIDTH];
+        }
+
+        // Split `A = S * D` with `S` sparse and `D` the identity on lane `0`.
+        // `D` commutes with the partial S-box and meets the previous round's
+        // MDS matrix, starting over with `D * M`.
+        let mut sparse_mds = vec![
+            SparseMds {
+                row: [F::zero(); WIDTH],
+                col: [F::zero(); WIDTH],
+            };
+            partial_rounds
+        ];
+        // `D` starts as the lower right block of `M`, and it gets multiplied by
+        // that block every round, so its inverse needs a single inversion.
+        let mds_inner: Vec<Vec<F>> = mds[1..].iter().map(|row| row[1..].to_vec()).collect();
+        let mds_inner_inv = invert(&mds_inner);
+        let mut dense = mds;
+        let mut inner = mds_inner.clone();
+        let mut inner_inv = mds_inner_inv.clone();
+        for round in (0..partial_rounds).rev() {
+            let sparse = &mut sparse_mds[round];
+            sparse.row[0] = dense[0][0];
+            for j in 1..WIDTH {
+                for k in 1..WIDTH {
+                    sparse.row[j] += dense[0][k] * inner_inv[k - 1][j - 1];
+                }
+                sparse.col[j] = dense[j][0];
+            }
+            if round == 0 {
+                break;
+            }
+
+            let mut next = [[F::zero(); WIDTH]; WIDTH];
+            next[0] = mds[0];
+            for i in 1..WIDTH {
+                for j in 0..WIDTH {
+                    for k in 1..WIDTH {
+                        next[i][j] += inner[i - 1][k - 1] * mds[k][j];
+                    }
+                }
+            }
+            dense = next;
+            inner = mul(&inner, &mds_inner);
+            inner_inv = mul(&mds_inner_inv, &inner_inv);
+        }
+        // The dense part left from the first partial round.
+        let mut pre_mds = [[F::zero(); WIDTH]; WIDTH];
+        pre_mds[0][0] = F::one();
+        for i in 1..WIDTH {
+            pre_mds[i][1..].copy_from_slice(&inner[i - 1]);
+        }
+
+        // The constants after the S-box go through the sparse matrix and are
+        // added before the next S-box.
+        let pre_mds_rows: Vec<Vec<F>> = pre_mds.iter().map(|row| row.to_vec()).collect();
+        let mut round_constants = vec![mul_vec(&pre_mds_rows, &constants[0])];
+        for round in 0..partial_rounds - 1 {
+            let sparse = &sparse_mds[round];
+            let mut next = [F::zero(); WIDTH];
+            next[0] = sparse.row[0] * after_sbox[round];
+            for i in 1..WIDTH {
+                next[i] = sparse.col[i] * after_sbox[round];
+            }
+            round_constants.push(next);
+        }
+
+        Self {
+            pre_mds,
+            round_constants,
+            sparse_mds,
+        }
+    }
+
+    /// `new` for `P`, computed on first use and shared by every permutation
+    /// with the same parameters afterwards. Each parameter set is leaked once.
+    pub fn cached<P: RoundParams<F, WIDTH>>() -> &'static Self {
+        type Cache = HashMap<TypeId, &'static (dyn Any + Send + Sync)>;
+        static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+
+        let mut cache = CACHE.get_or_init(Default::default).lock().unwrap();
+        let rounds = *cache.entry(TypeId::of::<(Self, P)>()).or_insert_with(|| {
+            let rounds: &'static (dyn Any + Send + Sync) = Box::leak(Box::new(Self::new::<P>()));
+            rounds
+        });
+        rounds.downcast_ref().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::params::{bn254_10x5::Params10x5Bn254, bn254_5x5::Params5x5Bn254};
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    fn apply_mds<const WIDTH: usize>(
+        state: &[Fr; WIDTH],
+        mds: &[[Fr; WIDTH]; WIDTH],
+    ) -> [Fr; WIDTH] {
+        let rows: Vec<Vec<Fr>> = mds.iter().map(|row| row.to_vec()).collect();
+        mul_vec(&rows, state)
+    }
+
+    /// Partial rounds as in the specification and as in `OptimizedRounds`.
+    fn partial_rounds<const WIDTH: usize, P>(inputs: [Fr; WIDTH]) -> ([Fr; WIDTH], [Fr; WIDTH])
+    where
+        P: RoundParams<Fr, WIDTH>,
+    {
+        let half_full_rounds = P::full_rounds() / 2;
+        let mds = P::mds();
+        let round_constants = P::round_constants();
+        let mut state = inputs;
+        for round in 0..P::partial_rounds() {
+            let round_consts = P::load_round_constants(half_full_rounds + round, &round_constants);
+            for i in 0..WIDTH {
+                state[i] += round_consts[i];
+            }
+            state[0] = P::sbox_f(state[0]);
+            state = apply_mds(&state, &mds);
+        }
+
+        let optimized = OptimizedRounds::<Fr, WIDTH>::new::<P>();
+        let mut optimized_state = apply_mds(&inputs, &optimized.pre_mds);
+        for round in 0..P::partial_rounds() {
+            for i in 0..WIDTH {
+                optimized_state[i] += optimized.round_constants[round][i];
+            }
+            optimized_state[0] = P::sbox_f(optimized_state[0]);
+            optimized_state = optimized.sparse_mds[round].apply(&optimized_state);
+        }
+
+        (state, optimized_state)
+    }
+
+    #[test]
+    fn should_match_dense_partial_rounds_5x5() {
+        let inputs = [1u64, 2, 3, 4, 5].map(Fr::from);
+        let (state, optimized_state) = partial_rounds::<5, Params5x5Bn254>(inputs);
+        assert_eq!(state, optimized_state);
+    }
+
+    #[test]
+    fn should_match_dense_partial_rounds_10x5() {
+        let inputs = [1u64, 2, 3, 4, 5, 6, 7, 8, 9, 10].map(Fr::from);
+        let (state, optimized_state) = partial_rounds::<10, Params10x5Bn254>(inputs);
+        assert_eq!(state, optimized_state);
+    }
+
+    #[test]
+    fn should_cache_per_parameter_set() {
+        let rounds = OptimizedRounds::<Fr, 5>::cached::<Params5x5Bn254>();
+        let again = OptimizedRounds::<Fr, 5>::cached::<Params5x5Bn254>();
+        assert!(std::ptr::eq(rounds, again));
+        assert_eq!(*rounds, OptimizedRounds::new::<Params5x5Bn254>());
+
+        let rounds = OptimizedRounds::<Fr, 10>::cached::<Params10x5Bn254>();
+        assert_eq!(*rounds, OptimizedRounds::new::<Params10x5Bn254>());
+    }
+
+    #[test]
+    fn should_keep_lane_zero_out_of_pre_mds() {
+        let optimized = OptimizedRounds::<Fr, 5>::new::<Params5x5Bn254>();
+        let mut lane_zero = [Fr::zero(); 5];
+        lane_zero[0] = Fr::one();
+        assert_eq!(optimized.pre_mds[0], lane_zero);
+        for row in &optimized.pre_mds[1..] {
+            assert_eq!(row[0], Fr::zero());
+        }
+    }
+}
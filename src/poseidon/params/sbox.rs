@@ -0,0 +1,374 @@
+use super::{grain::SboxType, PolySbox, Sbox, WitnessSbox};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::{Error, Expression};
+use maingate::{AssignedValue, MainGate, MainGateInstructions, RegionCtx, UnassignedValue};
+
+/// `x^3`, for the fields where `3` does not divide `p - 1`.
+pub struct Pow3;
+
+impl Sbox for Pow3 {
+    const SBOX: SboxType = SboxType::Pow(3);
+
+    fn sbox_asgn<F: FieldExt>(
+        main_gate: &MainGate<F>,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        exp: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        let exp2 = main_gate.mul(ctx, exp, exp)?;
+        main_gate.mul(ctx, &exp2, exp)
+    }
+
+    fn sbox_f<F: FieldExt>(f: F) -> F {
+        f * f * f
+    }
+}
+
+impl PolySbox for Pow3 {
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+        let exp2 = exp.clone() * exp.clone();
+        exp2 * exp
+    }
+
+    fn sbox_expr_from_square<F: FieldExt>(
+        exp: Expression<F>,
+        square: Expression<F>,
+    ) -> Expression<F> {
+        square * exp
+    }
+}
+
+/// `x^5`, for the fields where `5` does not divide `p - 1`.
+pub struct Pow5;
+
+impl Sbox for Pow5 {
+    const SBOX: SboxType = SboxType::Pow(5);
+
+    fn sbox_asgn<F: FieldExt>(
+        main_gate: &MainGate<F>,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        exp: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        let exp2 = main_gate.mul(ctx, exp, exp)?;
+        let exp4 = main_gate.mul(ctx, &exp2, &exp2)?;
+        main_gate.mul(ctx, &exp4, exp)
+    }
+
+    fn sbox_f<F: FieldExt>(f: F) -> F {
+        let f2 = f * f;
+        let f4 = f2 * f2;
+        f4 * f
+    }
+}
+
+impl PolySbox for Pow5 {
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+        let exp2 = exp.clone() * exp.clone();
+        let exp4 = exp2.clone() * exp2;
+        exp4 * exp
+    }
+
+    fn sbox_expr_from_square<F: FieldExt>(
+        exp: Expression<F>,
+        square: Expression<F>,
+    ) -> Expression<F> {
+        square.clone() * square * exp
+    }
+}
+
+/// `x^7`, for the fields where neither `3` nor `5` can be used.
+pub struct Pow7;
+
+impl Sbox for Pow7 {
+    const SBOX: SboxType = SboxType::Pow(7);
+
+    fn sbox_asgn<F: FieldExt>(
+        main_gate: &MainGate<F>,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        exp: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        let exp2 = main_gate.mul(ctx, exp, exp)?;
+        let exp3 = main_gate.mul(ctx, &exp2, exp)?;
+        let exp6 = main_gate.mul(ctx, &exp3, &exp3)?;
+        main_gate.mul(ctx, &exp6, exp)
+    }
+
+    fn sbox_f<F: FieldExt>(f: F) -> F {
+        let f3 = f * f * f;
+        f3 * f3 * f
+    }
+}
+
+impl PolySbox for Pow7 {
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+        let exp2 = exp.clone() * exp.clone();
+        let exp3 = exp2.clone() * exp.clone();
+        let exp6 = exp3.clone() * exp3;
+        exp6 * exp
+    }
+
+    fn sbox_expr_from_square<F: FieldExt>(
+        exp: Expression<F>,
+        square: Expression<F>,
+    ) -> Expression<F> {
+        square.clone() * square.clone() * square * exp
+    }
+}
+
+/// `x^-1`, with `0` mapped to itself. It is a permutation of every field, but
+/// not a polynomial of low degree, so it is only a `WitnessSbox`: the inverse
+/// is witnessed and checked with `sbox_constraints`.
+pub struct Inverse;
+
+impl Sbox for Inverse {
+    const SBOX: SboxType = SboxType::Inverse;
+
+    fn sbox_asgn<F: FieldExt>(
+        main_gate: &MainGate<F>,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        exp: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        let inverse = exp.value().map(|x| Self::sbox_f(*x));
+        let inverse = main_gate.assign_value(ctx, &UnassignedValue::from(inverse))?;
+        let product = main_gate.mul(ctx, exp, &inverse)?;
+        let exp_product = main_gate.mul(ctx, exp, &product)?;
+        main_gate.assert_equal(ctx, &exp_product, exp)?;
+        let inverse_product = main_gate.mul(ctx, &inverse, &product)?;
+        main_gate.assert_equal(ctx, &inverse_product, &inverse)?;
+        Ok(inverse)
+    }
+
+    fn sbox_f<F: FieldExt>(f: F) -> F {
+        f.invert().unwrap_or(F::zero())
+    }
+}
+
+impl WitnessSbox for Inverse {
+    /// `x * y = 1` when `x` is not zero. Otherwise `x * y - 1` is not zero and
+    /// forces `y` to zero.
+    fn sbox_constraints<F: FieldExt>(
+        input: Expression<F>,
+        output: Expression<F>,
+    ) -> Vec<Expression<F>> {
+        let not_one = input.clone() * output.clone() - Expression::Constant(F::one());
+        vec![input * not_one.clone(), output * not_one]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use halo2_proofs::{
+        arithmetic::Field,
+        circuit::{Layouter, Region, SimpleFloorPlanner},
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Advice, Circuit, Column, ConstraintSystem, Instance, Selector},
+        poly::Rotation,
+    };
+    use maingate::MainGateConfig;
+    use std::marker::PhantomData;
+
+    #[derive(Clone)]
+    struct SboxTesterConfig {
+        main_gate_config: MainGateConfig,
+        results: Column<Instance>,
+    }
+
+    /// Applies `sbox_asgn` of `S` to every input.
+    struct SboxTester<S: Sbox> {
+        inputs: [Option<Fr>; 3],
+        _sbox: PhantomData<S>,
+    }
+
+    impl<S: Sbox> SboxTester<S> {
+        fn new(inputs: [Option<Fr>; 3]) -> Self {
+            Self {
+                inputs,
+                _sbox: PhantomData,
+            }
+        }
+    }
+
+    impl<S: Sbox> Circuit<Fr> for SboxTester<S> {
+        type Config = SboxTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::new([None; 3])
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let main_gate_config = MainGate::<Fr>::configure(meta);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                main_gate_config,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.main_gate_config);
+
+            let outputs = layouter.assign_region(
+                || "sbox",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    self.inputs.try_map(|input| {
+                        let input = main_gate.assign_value(ctx, &UnassignedValue::from(input))?;
+                        S::sbox_asgn(&main_gate, ctx, &input)
+                    })
+                },
+            )?;
+
+            for (i, output) in outputs.iter().enumerate() {
+                layouter.constrain_instance(output.cell(), config.results, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn check_sbox_asgn<S: Sbox>() {
+        let inputs = [0u64, 2, 7].map(Fr::from);
+        let outputs = inputs.map(S::sbox_f);
+
+        let sbox_tester = SboxTester::<S>::new(inputs.map(Some));
+
+        let k = 6;
+        let prover = MockProver::run(k, &sbox_tester, vec![outputs.to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_compute_sbox_f() {
+        let x = Fr::from(7u64);
+        assert_eq!(Pow3::sbox_f(x), x.pow_vartime(&[3u64]));
+        assert_eq!(Pow5::sbox_f(x), x.pow_vartime(&[5u64]));
+        assert_eq!(Pow7::sbox_f(x), x.pow_vartime(&[7u64]));
+        assert_eq!(Inverse::sbox_f(x) * x, Fr::one());
+        assert_eq!(Inverse::sbox_f(Fr::zero()), Fr::zero());
+    }
+
+    #[test]
+    fn should_assign_sboxes() {
+        check_sbox_asgn::<Pow3>();
+        check_sbox_asgn::<Pow5>();
+        check_sbox_asgn::<Pow7>();
+        check_sbox_asgn::<Inverse>();
+    }
+
+    #[derive(Clone)]
+    struct ConstraintTesterConfig {
+        input: Column<Advice>,
+        output: Column<Advice>,
+        selector: Selector,
+    }
+
+    /// Checks a witnessed `output` against `input` with `sbox_constraints`.
+    struct ConstraintTester<S: WitnessSbox> {
+        input: Option<Fr>,
+        output: Option<Fr>,
+        _sbox: PhantomData<S>,
+    }
+
+    impl<S: WitnessSbox> ConstraintTester<S> {
+        fn new(input: Fr, output: Fr) -> Self {
+            Self {
+                input: Some(input),
+                output: Some(output),
+                _sbox: PhantomData,
+            }
+        }
+    }
+
+    impl<S: WitnessSbox> Circuit<Fr> for ConstraintTester<S> {
+        type Config = ConstraintTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                input: None,
+                output: None,
+                _sbox: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let input = meta.advice_column();
+            let output = meta.advice_column();
+            let selector = meta.selector();
+
+            meta.create_gate("sbox", |v_cells| {
+                let s = v_cells.query_selector(selector);
+                let input = v_cells.query_advice(input, Rotation::cur());
+                let output = v_cells.query_advice(output, Rotation::cur());
+                S::sbox_constraints(input, output)
+                    .into_iter()
+                    .map(|expr| s.clone() * expr)
+                    .collect::<Vec<_>>()
+            });
+
+            Self::Config {
+                input,
+                output,
+                selector,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "sbox",
+                |mut region: Region<'_, Fr>| {
+                    config.selector.enable(&mut region, 0)?;
+                    region.assign_advice(
+                        || "input",
+                        config.input,
+                        0,
+                        || self.input.ok_or(Error::Synthesis),
+                    )?;
+                    region.assign_advice(
+                        || "output",
+                        config.output,
+                        0,
+                        || self.output.ok_or(Error::Synthesis),
+                    )?;
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    fn verify_constraints<S: WitnessSbox>(input: Fr, output: Fr) -> bool {
+        let constraint_tester = ConstraintTester::<S>::new(input, output);
+        let prover = MockProver::run(4, &constraint_tester, vec![]).unwrap();
+        prover.verify().is_ok()
+    }
+
+    #[test]
+    fn should_constrain_inverse_witness() {
+        let x = Fr::from(7u64);
+        assert!(verify_constraints::<Inverse>(x, x.invert().unwrap()));
+        assert!(verify_constraints::<Inverse>(Fr::zero(), Fr::zero()));
+        assert!(!verify_constraints::<Inverse>(x, Fr::zero()));
+        assert!(!verify_constraints::<Inverse>(Fr::zero(), x));
+        assert!(!verify_constraints::<Inverse>(x, x));
+    }
+
+    #[test]
+    fn should_constrain_power_witness() {
+        let x = Fr::from(7u64);
+        assert!(verify_constraints::<Pow7>(x, Pow7::sbox_f(x)));
+        assert!(!verify_constraints::<Pow7>(x, Pow5::sbox_f(x)));
+    }
+}
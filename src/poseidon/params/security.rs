@@ -0,0 +1,267 @@
+use super::grain::{full_round_bounds, FullRoundBounds, SboxType};
+use super::RoundParams;
+use halo2_proofs::arithmetic::FieldExt;
+
+/// Parameters `validate` cannot check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecurityError {
+    /// The round bounds of the Poseidon paper only cover `x^alpha` S-boxes.
+    UnsupportedSbox(SboxType),
+}
+
+/// Outcome of `validate` for a `RoundParams` implementation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecurityReport {
+    /// Whether `x^alpha` is a permutation of the field, `gcd(alpha, p - 1) = 1`.
+    pub sbox_invertible: bool,
+    /// Whether there is a round constant for every lane of every round.
+    pub round_constants_complete: bool,
+    pub mds_invertible: bool,
+    /// Whether a non-trivial affine subspace of states keeps the S-box input
+    /// constant through any number of partial rounds.
+    pub infinite_subspace_trail: bool,
+    pub full_rounds: usize,
+    pub partial_rounds: usize,
+    /// Full rounds needed against each attack with these partial rounds.
+    pub bounds: FullRoundBounds,
+}
+
+impl SecurityReport {
+    /// Enough full rounds against differential and linear attacks.
+    pub fn statistical(&self) -> bool {
+        self.full_rounds >= self.bounds.statistical
+    }
+
+    /// Enough rounds against interpolation and Groebner basis attacks.
+    pub fn algebraic(&self) -> bool {
+        self.full_rounds >= self.bounds.interpolation.max(self.bounds.groebner)
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.sbox_invertible
+            && self.round_constants_complete
+            && self.mds_invertible
+            && !self.infinite_subspace_trail
+            && self.statistical()
+            && self.algebraic()
+    }
+}
+
+/// Rank of `rows` by Gaussian elimination.
+fn rank<F: FieldExt>(mut rows: Vec<Vec<F>>) -> usize {
+    let columns = rows.first().map_or(0, |row| row.len());
+    let mut rank = 0;
+    for column in 0..columns {
+        let pivot = match (rank..rows.len()).find(|i| rows[*i][column] != F::zero()) {
+            Some(pivot) => pivot,
+            None => continue,
+        };
+        rows.swap(rank, pivot);
+        let inv = rows[rank][column].invert().unwrap();
+        let pivot_row = rows[rank].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i == rank || row[column] == F::zero() {
+                continue;
+            }
+            let factor = row[column] * inv;
+            for (item, pivot_item) in row.iter_mut().zip(pivot_row.iter()).skip(column) {
+                *item -= *pivot_item * factor;
+            }
+        }
+        rank += 1;
+    }
+    rank
+}
+
+/// `(p - 1) mod alpha`, from the little-endian representation of `p - 1`.
+fn modulus_minus_one_rem<F: FieldExt>(alpha: u64) -> u64 {
+    let repr = (-F::one()).to_repr();
+    repr.as_ref()
+        .iter()
+        .rev()
+        .fold(0u128, |rem, b| ((rem << 8) + *b as u128) % alpha as u128) as u64
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Whether a non-trivial subspace of states skips the S-box of every partial
+/// round with this MDS matrix.
+///
+/// Partial rounds only apply the S-box to lane `0`, so the states skipping it
+/// for `r` rounds are the kernel of the rows `e_0 * M^i`, `i < r`. A subspace
+/// trail of any length exists exactly when these rows never reach full rank.
+pub fn infinite_subspace_trail<F: FieldExt, const WIDTH: usize>(mds: &[[F; WIDTH]; WIDTH]) -> bool {
+    let mut krylov = Vec::new();
+    let mut row = [F::zero(); WIDTH];
+    row[0] = F::one();
+    for _ in 0..WIDTH {
+        krylov.push(row.to_vec());
+        let mut next = [F::zero(); WIDTH];
+        for (j, lane) in next.iter_mut().enumerate() {
+            for (item, mds_row) in row.iter().zip(mds.iter()) {
+                *lane += *item * mds_row[j];
+            }
+        }
+        row = next;
+    }
+    rank(krylov) < WIDTH
+}
+
+/// Checks the parameters of `P` at `security` bits, with the exponent of its
+/// `x^alpha` S-box.
+pub fn validate<F: FieldExt, const WIDTH: usize, P>(
+    security: usize,
+) -> Result<SecurityReport, SecurityError>
+where
+    P: RoundParams<F, WIDTH>,
+{
+    let alpha = match P::SBOX {
+        SboxType::Pow(alpha) => alpha,
+        sbox => return Err(SecurityError::UnsupportedSbox(sbox)),
+    };
+    let mds = P::mds();
+    let full_rounds = P::full_rounds();
+    let partial_rounds = P::partial_rounds();
+
+    let sbox_invertible = alpha > 1 && gcd(alpha, modulus_minus_one_rem::<F>(alpha)) == 1;
+    let round_constants_complete = P::round_constants().len() == P::round_constants_count();
+
+    let rows = mds.iter().map(|row| row.to_vec()).collect();
+    let mds_invertible = rank(rows) == WIDTH;
+
+    let infinite_subspace_trail = infinite_subspace_trail(&mds);
+
+    Ok(SecurityReport {
+        sbox_invertible,
+        round_constants_complete,
+        mds_invertible,
+        infinite_subspace_trail,
+        full_rounds,
+        partial_rounds,
+        bounds: full_round_bounds::<F>(WIDTH, partial_rounds, alpha, security),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::params::{
+        bn254_10x5::Params10x5Bn254,
+        bn254_5x5::Params5x5Bn254,
+        grain::GrainParams,
+        sbox::{Inverse, Pow3},
+        PolySbox, Sbox,
+    };
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::plonk::{Error, Expression};
+    use maingate::{AssignedValue, MainGate, RegionCtx};
+
+    /// 3-wide parameters with a chosen MDS, reusing the 5x5 S-box.
+    struct TestParams<const M: usize>;
+
+    impl<const M: usize> Sbox for TestParams<M> {
+        const SBOX: SboxType = Params5x5Bn254::SBOX;
+
+        fn sbox_asgn<F: FieldExt>(
+            main_gate: &MainGate<F>,
+            ctx: &mut RegionCtx<'_, '_, F>,
+            exp: &AssignedValue<F>,
+        ) -> Result<AssignedValue<F>, Error> {
+            Params5x5Bn254::sbox_asgn(main_gate, ctx, exp)
+        }
+
+        fn sbox_f<F: FieldExt>(f: F) -> F {
+            Params5x5Bn254::sbox_f(f)
+        }
+    }
+
+    impl<const M: usize> PolySbox for TestParams<M> {
+        fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+            Params5x5Bn254::sbox_expr(exp)
+        }
+
+        fn sbox_expr_from_square<F: FieldExt>(
+            exp: Expression<F>,
+            square: Expression<F>,
+        ) -> Expression<F> {
+            Params5x5Bn254::sbox_expr_from_square(exp, square)
+        }
+    }
+
+    impl<const M: usize> RoundParams<Fr, 3> for TestParams<M> {
+        fn full_rounds() -> usize {
+            8
+        }
+
+        fn partial_rounds() -> usize {
+            57
+        }
+
+        fn round_constants() -> Vec<Fr> {
+            vec![Fr::one(); 65 * 3]
+        }
+
+        fn mds() -> [[Fr; 3]; 3] {
+            let (zero, one, two) = (Fr::zero(), Fr::one(), Fr::from(2));
+            match M {
+                // Singular.
+                0 => [
+                    [one, two, zero],
+                    [two, Fr::from(4), zero],
+                    [zero, zero, one],
+                ],
+                // Lanes `1` and `2` never feed lane `0`.
+                1 => [[two, zero, zero], [one, two, one], [one, one, two]],
+                _ => [[two, one, one], [one, two, one], [one, one, two + one]],
+            }
+        }
+    }
+
+    #[test]
+    fn should_validate_5x5_params() {
+        let report = validate::<Fr, 5, Params5x5Bn254>(128).unwrap();
+        assert!(report.is_secure(), "{:?}", report);
+    }
+
+    #[test]
+    fn should_validate_10x5_params() {
+        let report = validate::<Fr, 10, Params10x5Bn254>(128).unwrap();
+        assert!(report.is_secure(), "{:?}", report);
+    }
+
+    #[test]
+    fn should_reject_weak_params() {
+        // `3` divides `p - 1` in bn254.
+        let report = validate::<Fr, 5, GrainParams<Pow3, 8, 60>>(128).unwrap();
+        assert!(!report.sbox_invertible);
+
+        let report = validate::<Fr, 3, TestParams<0>>(128).unwrap();
+        assert!(!report.mds_invertible);
+        let report = validate::<Fr, 3, TestParams<1>>(128).unwrap();
+        assert!(report.mds_invertible);
+        assert!(report.infinite_subspace_trail);
+        let report = validate::<Fr, 3, TestParams<2>>(128).unwrap();
+        assert!(report.is_secure(), "{:?}", report);
+
+        let report = validate::<Fr, 5, GrainParams<Params5x5Bn254, 8, 20>>(128).unwrap();
+        assert!(report.statistical());
+        assert!(!report.algebraic());
+        let report = validate::<Fr, 5, GrainParams<Params5x5Bn254, 4, 60>>(128).unwrap();
+        assert!(!report.statistical());
+    }
+
+    #[test]
+    fn should_not_validate_inverse_sbox() {
+        assert_eq!(
+            validate::<Fr, 5, GrainParams<Inverse, 8, 60>>(128),
+            Err(SecurityError::UnsupportedSbox(SboxType::Inverse))
+        );
+    }
+}
@@ -1,50 +1,77 @@
 use std::marker::PhantomData;
 
-use super::params::RoundParams;
-use super::PoseidonConfig;
-use crate::poseidon::PoseidonChip;
+use super::native::sponge::domain_tag;
+use crate::hasher::GateHasher;
 use halo2_proofs::circuit::Layouter;
 use halo2_proofs::circuit::Region;
 use halo2_proofs::plonk::Advice;
 use halo2_proofs::plonk::ConstraintSystem;
 use halo2_proofs::plonk::Expression;
-use halo2_proofs::plonk::{Column, Selector};
+use halo2_proofs::plonk::{Column, Fixed, Selector};
 use halo2_proofs::poly::Rotation;
 use halo2_proofs::{arithmetic::FieldExt, circuit::AssignedCell, plonk::Error};
 
 #[derive(Clone)]
-struct PoseidonSpongeConfig<const WIDTH: usize> {
-    poseidon_config: PoseidonConfig<WIDTH>,
+struct SpongeConfig<C, const WIDTH: usize> {
+    hasher_config: C,
     state: [Column<Advice>; WIDTH],
+    /// State before the chunk is added, copied from the previous permutation.
+    previous: [Column<Advice>; WIDTH],
+    constants: Column<Fixed>,
     absorb_selector: Selector,
 }
 
-struct PoseidonSpongeChip<F: FieldExt, const WIDTH: usize, P>
+/// Sponge with the same capacity, domain tag, padding and duplexing as the
+/// native `Sponge`, absorbing with a custom gate and permuting with the
+/// custom-gate chip of `H`.
+struct SpongeChip<F: FieldExt, const WIDTH: usize, H>
 where
-    P: RoundParams<F, WIDTH>,
+    H: GateHasher<F, WIDTH>,
 {
+    domain: u64,
+    /// Assigned on the first squeeze.
+    state: Option<[AssignedCell<F, F>; WIDTH]>,
     inputs: Vec<AssignedCell<F, F>>,
-    _params: PhantomData<P>,
+    /// Whether inputs were added since the last squeeze.
+    absorbing: bool,
+    /// Number of rate lanes of the state already squeezed.
+    squeezed: usize,
+    _hasher: PhantomData<H>,
 }
 
-impl<F: FieldExt, const WIDTH: usize, P> PoseidonSpongeChip<F, WIDTH, P>
+impl<F: FieldExt, const WIDTH: usize, H> SpongeChip<F, WIDTH, H>
 where
-    P: RoundParams<F, WIDTH>,
+    H: GateHasher<F, WIDTH>,
 {
+    const RATE: usize = WIDTH - 1;
+
     fn new() -> Self {
+        Self::with_domain(0)
+    }
+
+    fn with_domain(domain: u64) -> Self {
+        assert!(WIDTH > 1);
         Self {
+            domain,
+            state: None,
             inputs: Vec::new(),
-            _params: PhantomData,
+            absorbing: true,
+            squeezed: 0,
+            _hasher: PhantomData,
         }
     }
 
-    fn configure(meta: &mut ConstraintSystem<F>) -> PoseidonSpongeConfig<WIDTH> {
-        let poseidon_config = PoseidonChip::<_, WIDTH, P>::configure(meta);
-        let state = [(); WIDTH].map(|_| {
-            let column = meta.advice_column();
-            meta.enable_equality(column);
-            column
+    fn configure(meta: &mut ConstraintSystem<F>) -> SpongeConfig<H::Config, WIDTH> {
+        let hasher_config = H::configure(meta);
+        let [state, previous] = [(); 2].map(|_| {
+            [(); WIDTH].map(|_| {
+                let column = meta.advice_column();
+                meta.enable_equality(column);
+                column
+            })
         });
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
         let absorb_selector = meta.selector();
 
         meta.create_gate("absorb", |v_cells| {
@@ -52,69 +79,108 @@ where
 
             let s = v_cells.query_selector(absorb_selector);
             for i in 0..WIDTH {
-                let poseidon_exp = v_cells.query_advice(poseidon_config.state[i], Rotation::cur());
+                let previous_exp = v_cells.query_advice(previous[i], Rotation::cur());
                 let sponge_exp = v_cells.query_advice(state[i], Rotation::cur());
                 let next_sponge_exp = v_cells.query_advice(state[i], Rotation::next());
-                let diff = next_sponge_exp - (sponge_exp + poseidon_exp);
+                let diff = next_sponge_exp - (sponge_exp + previous_exp);
                 exprs[i] = s.clone() * diff;
             }
 
             exprs
         });
 
-        PoseidonSpongeConfig {
-            poseidon_config,
+        SpongeConfig {
+            hasher_config,
             state,
+            previous,
+            constants,
             absorb_selector,
         }
     }
 
+    /// Copies `cells` into `columns` at `round`, lanes without a cell are fixed
+    /// to the matching entry of `constants`.
     fn load_state(
         columns: [Column<Advice>; WIDTH],
         region: &mut Region<'_, F>,
         round: usize,
-        prev_state: &[AssignedCell<F, F>],
+        cells: [Option<&AssignedCell<F, F>>; WIDTH],
+        constants: [F; WIDTH],
     ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
         let mut state: [Option<AssignedCell<F, F>>; WIDTH] = [(); WIDTH].map(|_| None);
         for i in 0..WIDTH {
-			if let Some(cell) = prev_state.get(i) {
-				state[i] = Some(cell.copy_advice(|| "state", region, columns[i], round)?);
-			} else {
-				state[i] = Some(region.assign_advice(|| "state", columns[i], round, || Ok(F::zero()))?);
-			}
+            state[i] = Some(match cells[i] {
+                Some(cell) => cell.copy_advice(|| "state", region, columns[i], round)?,
+                None => region.assign_advice_from_constant(
+                    || "state",
+                    columns[i],
+                    round,
+                    constants[i],
+                )?,
+            });
         }
         Ok(state.map(|item| item.unwrap()))
     }
 
     fn update(&mut self, inputs: &[AssignedCell<F, F>]) {
         self.inputs.extend_from_slice(inputs);
+        self.absorbing = true;
     }
 
-    pub fn squeeze(
+    fn initial_state(
         &self,
-        config: &PoseidonSpongeConfig<WIDTH>,
-        mut layouter: impl Layouter<F>,
-    ) -> Result<AssignedCell<F, F>, Error> {
-        assert!(self.inputs.len() > 0);
+        config: &SpongeConfig<H::Config, WIDTH>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        let mut init_state = [F::zero(); WIDTH];
+        init_state[0] = domain_tag(self.domain);
+        layouter.assign_region(
+            || "load_chunks",
+            |mut region: Region<'_, F>| {
+                Self::load_state(config.state, &mut region, 0, [None; WIDTH], init_state)
+            },
+        )
+    }
 
-		let mut state = layouter.assign_region(
-			|| "load_chunks",
-			|mut region: Region<'_, F>| Self::load_state(config.state, &mut region, 0, &[])
-		)?;
+    /// Adds the pending inputs with their `10*` padding to the rate lanes of
+    /// `state`, permuting after every chunk.
+    fn absorb(
+        &self,
+        config: &SpongeConfig<H::Config, WIDTH>,
+        layouter: &mut impl Layouter<F>,
+        mut state: [AssignedCell<F, F>; WIDTH],
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        // `10*` padding: a one right after the inputs, zeros up to the rate.
+        let num_chunks = self.inputs.len() / Self::RATE + 1;
+        for i in 0..num_chunks {
+            let mut chunk = [None; WIDTH];
+            let mut padding = [F::zero(); WIDTH];
+            for j in 0..Self::RATE {
+                let pos = i * Self::RATE + j;
+                chunk[j + 1] = self.inputs.get(pos);
+                if pos == self.inputs.len() {
+                    padding[j + 1] = F::one();
+                }
+            }
 
-        for (i, chunk) in self.inputs.chunks(WIDTH).enumerate() {
-			let inputs = layouter.assign_region(
+            let inputs = layouter.assign_region(
                 || format!("absorb_{}", i),
                 |mut region: Region<'_, F>| {
                     let round = 0;
+                    let mut state_cells = [None; WIDTH];
+                    for k in 0..WIDTH {
+                        state_cells[k] = Some(&state[k]);
+                    }
                     config.absorb_selector.enable(&mut region, round)?;
 
-                    let loaded_chunk = Self::load_state(config.state, &mut region, round, chunk)?;
+                    let loaded_chunk =
+                        Self::load_state(config.state, &mut region, round, chunk, padding)?;
                     let loaded_state = Self::load_state(
-                        config.poseidon_config.state,
+                        config.previous,
                         &mut region,
                         round,
-                        &state,
+                        state_cells,
+                        [F::zero(); WIDTH],
                     )?;
 
                     let next_state = loaded_chunk.zip(loaded_state).zip(config.state).try_map(
@@ -135,23 +201,68 @@ where
                 },
             )?;
 
-            let pos = PoseidonChip::<_, WIDTH, P>::new(inputs);
-            state = pos.permute(
-                &config.poseidon_config,
+            state = H::permute_cells(
+                &config.hasher_config,
                 layouter.namespace(|| format!("absorb_{}", i)),
+                inputs,
             )?;
         }
 
-        Ok(state[0].clone())
+        Ok(state)
+    }
+
+    /// Next rate lane of the state. Inputs added since the last squeeze are
+    /// absorbed first, once the rate lanes are used up the state is permuted.
+    pub fn squeeze(
+        &mut self,
+        config: &SpongeConfig<H::Config, WIDTH>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let mut state = match self.state.take() {
+            Some(state) => state,
+            None => self.initial_state(config, &mut layouter)?,
+        };
+
+        if self.absorbing {
+            state = self.absorb(config, &mut layouter, state)?;
+            self.inputs.clear();
+            self.absorbing = false;
+            self.squeezed = 0;
+        } else if self.squeezed == Self::RATE {
+            state = H::permute_cells(
+                &config.hasher_config,
+                layouter.namespace(|| "squeeze"),
+                state,
+            )?;
+            self.squeezed = 0;
+        }
+
+        self.squeezed += 1;
+        let output = state[self.squeezed].clone();
+        self.state = Some(state);
+        Ok(output)
+    }
+
+    pub fn squeeze_n(
+        &mut self,
+        config: &SpongeConfig<H::Config, WIDTH>,
+        mut layouter: impl Layouter<F>,
+        n: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        (0..n)
+            .map(|i| self.squeeze(config, layouter.namespace(|| format!("squeeze_{}", i))))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{PoseidonSpongeChip, PoseidonSpongeConfig};
-    use crate::poseidon::native::sponge::PoseidonSponge;
-
+    use super::{SpongeChip, SpongeConfig};
+    use crate::hasher::GateHasher;
+    use crate::poseidon::native::{sponge::Sponge, Poseidon};
     use crate::poseidon::params::{bn254_5x5::Params5x5Bn254, hex_to_field};
+    use crate::rescue::{native::RescuePrime, params as rescue_params};
+    use std::marker::PhantomData;
 
     use halo2_proofs::{
         circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner},
@@ -160,33 +271,35 @@ mod test {
         plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
     };
 
-    type TestPoseidonSponge = PoseidonSponge<Fr, 5, Params5x5Bn254>;
+    type TestPoseidon = Poseidon<Fr, 5, Params5x5Bn254>;
 
-    type TestPoseidonSpongeChip = PoseidonSpongeChip<Fr, 5, Params5x5Bn254>;
+    type TestRescuePrime = RescuePrime<Fr, 5, rescue_params::Params5x5Bn254>;
 
     #[derive(Clone)]
-    struct PoseidonTesterConfig {
-        sponge: PoseidonSpongeConfig<5>,
+    struct SpongeTesterConfig<C> {
+        sponge: SpongeConfig<C, 5>,
         results: Column<Instance>,
     }
 
-    struct PoseidonTester {
+    struct SpongeTester<H: GateHasher<Fr, 5>> {
         inputs1: [Option<Fr>; 5],
         inputs2: [Option<Fr>; 5],
+        _hasher: PhantomData<H>,
     }
 
-    impl PoseidonTester {
+    impl<H: GateHasher<Fr, 5>> SpongeTester<H> {
         fn new(inputs1: [Fr; 5], inputs2: [Fr; 5]) -> Self {
             Self {
                 inputs1: inputs1.map(|item| Some(item)),
                 inputs2: inputs2.map(|item| Some(item)),
+                _hasher: PhantomData,
             }
         }
     }
 
-    impl PoseidonTester {
+    impl<H: GateHasher<Fr, 5>> SpongeTester<H> {
         fn load_state(
-            config: &PoseidonSpongeConfig<5>,
+            config: &SpongeConfig<H::Config, 5>,
             region: &mut Region<'_, Fr>,
             round: usize,
             init_state: [Option<Fr>; 5],
@@ -204,19 +317,20 @@ mod test {
         }
     }
 
-    impl Circuit<Fr> for PoseidonTester {
-        type Config = PoseidonTesterConfig;
+    impl<H: GateHasher<Fr, 5>> Circuit<Fr> for SpongeTester<H> {
+        type Config = SpongeTesterConfig<H::Config>;
         type FloorPlanner = SimpleFloorPlanner;
 
         fn without_witnesses(&self) -> Self {
             Self {
                 inputs1: [None; 5],
                 inputs2: [None; 5],
+                _hasher: PhantomData,
             }
         }
 
         fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
-            let sponge_config = TestPoseidonSpongeChip::configure(meta);
+            let sponge_config = SpongeChip::<Fr, 5, H>::configure(meta);
             let results = meta.instance_column();
 
             meta.enable_equality(results);
@@ -246,19 +360,88 @@ mod test {
                 },
             )?;
 
-            let mut poseidon_sponge = TestPoseidonSpongeChip::new();
-            poseidon_sponge.update(&inputs1);
-            poseidon_sponge.update(&inputs2);
-            let result_state = poseidon_sponge
-                .squeeze(&config.sponge, layouter.namespace(|| "poseidon_sponge"))?;
+            let mut sponge = SpongeChip::<Fr, 5, H>::new();
+            sponge.update(&inputs1);
+            sponge.update(&inputs2);
+            let result_state = sponge.squeeze(&config.sponge, layouter.namespace(|| "sponge"))?;
 
             layouter.constrain_instance(result_state.cell(), config.results, 0)?;
             Ok(())
         }
     }
 
-    #[test]
-    fn should_match_native_sponge() {
+    /// Absorbs `inputs1`, squeezes `n1` outputs, absorbs `inputs2` and squeezes
+    /// `n2` more.
+    struct DuplexTester<H: GateHasher<Fr, 5>> {
+        inputs1: Vec<Option<Fr>>,
+        n1: usize,
+        inputs2: Vec<Option<Fr>>,
+        n2: usize,
+        _hasher: PhantomData<H>,
+    }
+
+    impl<H: GateHasher<Fr, 5>> Circuit<Fr> for DuplexTester<H> {
+        type Config = SpongeTesterConfig<H::Config>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                inputs1: vec![None; self.inputs1.len()],
+                n1: self.n1,
+                inputs2: vec![None; self.inputs2.len()],
+                n2: self.n2,
+                _hasher: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            SpongeTester::<H>::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let mut sponge = SpongeChip::<Fr, 5, H>::new();
+
+            let mut outputs = Vec::new();
+            for (i, (inputs, n)) in [(&self.inputs1, self.n1), (&self.inputs2, self.n2)]
+                .into_iter()
+                .enumerate()
+            {
+                let assigned = layouter.assign_region(
+                    || format!("load_inputs_{}", i),
+                    |mut region: Region<'_, Fr>| {
+                        let mut assigned = Vec::new();
+                        for (row, val) in inputs.iter().enumerate() {
+                            assigned.push(region.assign_advice(
+                                || "input",
+                                config.sponge.state[0],
+                                row,
+                                || val.ok_or(Error::Synthesis),
+                            )?);
+                        }
+                        Ok(assigned)
+                    },
+                )?;
+
+                sponge.update(&assigned);
+                outputs.extend(sponge.squeeze_n(
+                    &config.sponge,
+                    layouter.namespace(|| format!("squeeze_{}", i)),
+                    n,
+                )?);
+            }
+
+            for (i, output) in outputs.iter().enumerate() {
+                layouter.constrain_instance(output.cell(), config.results, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn assert_matches_native_sponge<H: GateHasher<Fr, 5>>() {
         let inputs1: [Fr; 5] = [
             "0x0000000000000000000000000000000000000000000000000000000000000000",
             "0x0000000000000000000000000000000000000000000000000000000000000001",
@@ -277,16 +460,51 @@ mod test {
         ]
         .map(|n| hex_to_field(n));
 
-        let mut sponge = TestPoseidonSponge::new();
+        let mut sponge = Sponge::<Fr, 5, H>::new();
         sponge.update(&inputs1);
         sponge.update(&inputs2);
 
         let native_result = sponge.squeeze();
 
-        let poseidon_sponge = PoseidonTester::new(inputs1, inputs2);
+        let sponge_tester = SpongeTester::<H>::new(inputs1, inputs2);
 
         let k = 9;
-        let prover = MockProver::run(k, &poseidon_sponge, vec![vec![native_result]]).unwrap();
+        let prover = MockProver::run(k, &sponge_tester, vec![vec![native_result]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_match_native_sponge() {
+        assert_matches_native_sponge::<TestPoseidon>();
+        assert_matches_native_sponge::<TestRescuePrime>();
+    }
+
+    fn assert_matches_native_duplex<H: GateHasher<Fr, 5>>() {
+        let inputs1: Vec<Fr> = (0..3u64).map(|n| Fr::from(n)).collect();
+        let inputs2: Vec<Fr> = (3..9u64).map(|n| Fr::from(n)).collect();
+
+        let mut sponge = Sponge::<Fr, 5, H>::new();
+        sponge.update(&inputs1);
+        let mut outputs = sponge.squeeze_n(6);
+        sponge.update(&inputs2);
+        outputs.extend(sponge.squeeze_n(2));
+
+        let duplex_tester = DuplexTester {
+            inputs1: inputs1.iter().map(|x| Some(*x)).collect(),
+            n1: 6,
+            inputs2: inputs2.iter().map(|x| Some(*x)).collect(),
+            n2: 2,
+            _hasher: PhantomData::<H>,
+        };
+
+        let k = 10;
+        let prover = MockProver::run(k, &duplex_tester, vec![outputs]).unwrap();
         assert_eq!(prover.verify(), Ok(()));
     }
+
+    #[test]
+    fn should_match_native_duplex() {
+        assert_matches_native_duplex::<TestPoseidon>();
+        assert_matches_native_duplex::<TestRescuePrime>();
+    }
 }
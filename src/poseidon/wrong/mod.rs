@@ -0,0 +1,292 @@
+pub mod sponge;
+pub mod var_len;
+
+use super::params::{
+    optimized::{OptimizedRounds, SparseMds},
+    RoundParams,
+};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::Error;
+use maingate::{AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RegionCtx, Term};
+use std::marker::PhantomData;
+
+/// Poseidon permutation expressed purely with `MainGate` operations, so it can
+/// share columns and regions with the ECC and ECDSA chips.
+pub struct PoseidonChip<F: FieldExt, const WIDTH: usize, P>
+where
+    P: RoundParams<F, WIDTH>,
+{
+    main_gate: MainGate<F>,
+    _params: PhantomData<P>,
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> PoseidonChip<F, WIDTH, P>
+where
+    P: RoundParams<F, WIDTH>,
+{
+    pub fn new(main_gate_config: MainGateConfig) -> Self {
+        PoseidonChip {
+            main_gate: MainGate::new(main_gate_config),
+            _params: PhantomData,
+        }
+    }
+
+    fn apply_round_constants(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        state: &[AssignedValue<F>; WIDTH],
+        round_consts: &[F; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        let mut next_state = state.clone();
+        for i in 0..WIDTH {
+            next_state[i] = self
+                .main_gate
+                .add_constant(ctx, &state[i], round_consts[i])?;
+        }
+        Ok(next_state)
+    }
+
+    fn apply_mds(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        state: &[AssignedValue<F>; WIDTH],
+        mds: &[[F; WIDTH]; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        let mut new_state = state.clone();
+        // Compute mds matrix
+        for i in 0..WIDTH {
+            let terms: Vec<Term<F>> = state
+                .iter()
+                .zip(mds[i])
+                .map(|(s, mds_ij)| Term::Assigned(s, mds_ij))
+                .collect();
+            new_state[i] = self.main_gate.compose(ctx, &terms, F::zero())?;
+        }
+        Ok(new_state)
+    }
+
+    /// `matrix * state + constants`, leaving out the zero entries of `matrix`.
+    fn apply_matrix(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        state: &[AssignedValue<F>; WIDTH],
+        matrix: &[[F; WIDTH]; WIDTH],
+        constants: &[F; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        let mut new_state = state.clone();
+        for i in 0..WIDTH {
+            let terms: Vec<Term<F>> = state
+                .iter()
+                .zip(matrix[i])
+                .filter(|(_, m_ij)| *m_ij != F::zero())
+                .map(|(s, m_ij)| Term::Assigned(s, m_ij))
+                .collect();
+            new_state[i] = self.main_gate.compose(ctx, &terms, constants[i])?;
+        }
+        Ok(new_state)
+    }
+
+    /// `sparse * state + constants`, two terms for every lane but the first.
+    fn apply_sparse_mds(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        state: &[AssignedValue<F>; WIDTH],
+        sparse: &SparseMds<F, WIDTH>,
+        constants: &[F; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        let mut new_state = state.clone();
+        let terms: Vec<Term<F>> = state
+            .iter()
+            .zip(sparse.row)
+            .map(|(s, row_j)| Term::Assigned(s, row_j))
+            .collect();
+        new_state[0] = self.main_gate.compose(ctx, &terms, constants[0])?;
+        for i in 1..WIDTH {
+            let terms = [
+                Term::Assigned(&state[0], sparse.col[i]),
+                Term::Assigned(&state[i], F::one()),
+            ];
+            new_state[i] = self.main_gate.compose(ctx, &terms, constants[i])?;
+        }
+        Ok(new_state)
+    }
+
+    pub fn permute(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        inputs: [AssignedValue<F>; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        let full_rounds = P::full_rounds();
+        let half_full_rounds = full_rounds / 2;
+        let partial_rounds = P::partial_rounds();
+        let mds = P::mds();
+        let round_constants = P::round_constants();
+        let total_count = P::round_constants_count();
+
+        let first_round_end = half_full_rounds * WIDTH;
+        let first_round_constants = &round_constants[0..first_round_end];
+
+        let second_round_end = first_round_end + partial_rounds * WIDTH;
+        let third_round_constants = &round_constants[second_round_end..total_count];
+        let optimized = OptimizedRounds::cached::<P>();
+
+        let mut state = inputs;
+        for round in 0..half_full_rounds {
+            let round_consts = P::load_round_constants(round, first_round_constants);
+            state = self.apply_round_constants(ctx, &state, &round_consts)?;
+            for i in 0..WIDTH {
+                state[i] = P::sbox_asgn(&self.main_gate, ctx, &state[i])?;
+            }
+            state = self.apply_mds(ctx, &state, &mds)?;
+        }
+
+        // The constants of every partial round are added along with the matrix
+        // of the round before.
+        let no_constants = [F::zero(); WIDTH];
+        let pre_constants = &optimized.round_constants[0];
+        state = self.apply_matrix(ctx, &state, &optimized.pre_mds, pre_constants)?;
+        for round in 0..partial_rounds {
+            state[0] = P::sbox_asgn(&self.main_gate, ctx, &state[0])?;
+            let sparse = &optimized.sparse_mds[round];
+            let next_constants = optimized.round_constants.get(round + 1);
+            let next_constants = next_constants.unwrap_or(&no_constants);
+            state = self.apply_sparse_mds(ctx, &state, sparse, next_constants)?;
+        }
+
+        for round in 0..half_full_rounds {
+            let round_consts = P::load_round_constants(round, third_round_constants);
+            state = self.apply_round_constants(ctx, &state, &round_consts)?;
+            for i in 0..WIDTH {
+                state[i] = P::sbox_asgn(&self.main_gate, ctx, &state[i])?;
+            }
+            state = self.apply_mds(ctx, &state, &mds)?;
+        }
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::native::Poseidon;
+    use crate::poseidon::params::{
+        bn254_10x5::Params10x5Bn254, bn254_5x5::Params5x5Bn254, hex_to_field,
+    };
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Column, ConstraintSystem, Instance},
+    };
+    use maingate::UnassignedValue;
+
+    #[derive(Clone)]
+    struct PoseidonTesterConfig {
+        main_gate_config: MainGateConfig,
+        results: Column<Instance>,
+    }
+
+    struct PoseidonTester<const WIDTH: usize, P>
+    where
+        P: RoundParams<Fr, WIDTH>,
+    {
+        inputs: [Option<Fr>; WIDTH],
+        _params: PhantomData<P>,
+    }
+
+    impl<const WIDTH: usize, P> PoseidonTester<WIDTH, P>
+    where
+        P: RoundParams<Fr, WIDTH>,
+    {
+        fn new(inputs: [Option<Fr>; WIDTH]) -> Self {
+            Self {
+                inputs,
+                _params: PhantomData,
+            }
+        }
+    }
+
+    impl<const WIDTH: usize, P> Circuit<Fr> for PoseidonTester<WIDTH, P>
+    where
+        P: RoundParams<Fr, WIDTH>,
+    {
+        type Config = PoseidonTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::new([None; WIDTH])
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let main_gate_config = MainGate::<Fr>::configure(meta);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                main_gate_config,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.main_gate_config.clone());
+            let poseidon = PoseidonChip::<Fr, WIDTH, P>::new(config.main_gate_config);
+
+            let result_state = layouter.assign_region(
+                || "poseidon",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    let inputs = self
+                        .inputs
+                        .try_map(|val| main_gate.assign_value(ctx, &UnassignedValue::from(val)))?;
+                    poseidon.permute(ctx, inputs)
+                },
+            )?;
+
+            for i in 0..WIDTH {
+                layouter.constrain_instance(result_state[i].cell(), config.results, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_match_native_poseidon_5x5() {
+        let inputs: [Fr; 5] = [
+            "0x0000000000000000000000000000000000000000000000000000000000000000",
+            "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "0x0000000000000000000000000000000000000000000000000000000000000002",
+            "0x0000000000000000000000000000000000000000000000000000000000000003",
+            "0x0000000000000000000000000000000000000000000000000000000000000004",
+        ]
+        .map(|n| hex_to_field(n));
+
+        let outputs = Poseidon::<Fr, 5, Params5x5Bn254>::new(inputs).permute();
+
+        let poseidon_tester = PoseidonTester::<5, Params5x5Bn254>::new(inputs.map(|x| Some(x)));
+
+        let k = 12;
+        let prover = MockProver::run(k, &poseidon_tester, vec![outputs.to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_match_native_poseidon_10x5() {
+        let inputs: [Fr; 10] = [0u64, 1, 2, 3, 4, 5, 6, 7, 8, 9].map(|n| Fr::from(n));
+
+        let outputs = Poseidon::<Fr, 10, Params10x5Bn254>::new(inputs).permute();
+
+        let poseidon_tester = PoseidonTester::<10, Params10x5Bn254>::new(inputs.map(|x| Some(x)));
+
+        let k = 13;
+        let prover = MockProver::run(k, &poseidon_tester, vec![outputs.to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+}
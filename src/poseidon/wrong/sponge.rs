@@ -0,0 +1,326 @@
+use crate::hasher::Hasher;
+use crate::poseidon::native::{sponge::domain_tag, Poseidon};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::Error;
+use maingate::{AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RegionCtx};
+use std::marker::PhantomData;
+
+/// `MainGate` counterpart of the native `Sponge`.
+pub struct SpongeChip<F: FieldExt, const WIDTH: usize, H>
+where
+    H: Hasher<F, WIDTH>,
+{
+    main_gate_config: MainGateConfig,
+    domain: u64,
+    /// Assigned on the first squeeze.
+    state: Option<[AssignedValue<F>; WIDTH]>,
+    inputs: Vec<AssignedValue<F>>,
+    /// Whether inputs were added since the last squeeze.
+    absorbing: bool,
+    /// Number of rate lanes of the state already squeezed.
+    squeezed: usize,
+    _hasher: PhantomData<H>,
+}
+
+/// Sponge chip over the Poseidon permutation with the parameters `P`.
+pub type PoseidonSpongeChip<F, const WIDTH: usize, P> = SpongeChip<F, WIDTH, Poseidon<F, WIDTH, P>>;
+
+impl<F: FieldExt, const WIDTH: usize, H> SpongeChip<F, WIDTH, H>
+where
+    H: Hasher<F, WIDTH>,
+{
+    pub const RATE: usize = WIDTH - 1;
+
+    pub fn new(main_gate_config: MainGateConfig) -> Self {
+        Self::with_domain(main_gate_config, 0)
+    }
+
+    pub fn with_domain(main_gate_config: MainGateConfig, domain: u64) -> Self {
+        assert!(WIDTH > 1);
+        Self {
+            main_gate_config,
+            domain,
+            state: None,
+            inputs: Vec::new(),
+            absorbing: true,
+            squeezed: 0,
+            _hasher: PhantomData,
+        }
+    }
+
+    pub fn update(&mut self, inputs: &[AssignedValue<F>]) {
+        self.inputs.extend_from_slice(inputs);
+        self.absorbing = true;
+    }
+
+    /// Inputs followed by the `10*` padding, assigned as constants.
+    fn pad(&self, ctx: &mut RegionCtx<'_, '_, F>) -> Result<Vec<AssignedValue<F>>, Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let mut padded = self.inputs.clone();
+        padded.push(main_gate.assign_constant(ctx, F::one())?);
+        while padded.len() % Self::RATE != 0 {
+            padded.push(main_gate.assign_constant(ctx, F::zero())?);
+        }
+        Ok(padded)
+    }
+
+    fn initial_state(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let zero = main_gate.assign_constant(ctx, F::zero())?;
+        let mut state = [(); WIDTH].map(|_| zero.clone());
+        state[0] = main_gate.assign_constant(ctx, domain_tag(self.domain))?;
+        Ok(state)
+    }
+
+    pub fn squeeze(&mut self, ctx: &mut RegionCtx<'_, '_, F>) -> Result<AssignedValue<F>, Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+
+        let mut state = match self.state.take() {
+            Some(state) => state,
+            None => self.initial_state(ctx)?,
+        };
+
+        if self.absorbing {
+            for chunk in self.pad(ctx)?.chunks(Self::RATE) {
+                let mut input = state.clone();
+                for (i, item) in chunk.iter().enumerate() {
+                    input[i + 1] = main_gate.add(ctx, &state[i + 1], item)?;
+                }
+                state = H::permute_assigned(&self.main_gate_config, ctx, input)?;
+            }
+            self.inputs.clear();
+            self.absorbing = false;
+            self.squeezed = 0;
+        } else if self.squeezed == Self::RATE {
+            state = H::permute_assigned(&self.main_gate_config, ctx, state)?;
+            self.squeezed = 0;
+        }
+
+        self.squeezed += 1;
+        let output = state[self.squeezed].clone();
+        self.state = Some(state);
+        Ok(output)
+    }
+
+    pub fn squeeze_n(
+        &mut self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        n: usize,
+    ) -> Result<Vec<AssignedValue<F>>, Error> {
+        (0..n).map(|_| self.squeeze(ctx)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::native::sponge::PoseidonSponge;
+    use crate::poseidon::params::bn254_5x5::Params5x5Bn254;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Column, ConstraintSystem, Instance},
+    };
+    use maingate::UnassignedValue;
+
+    type TestPoseidonSponge = PoseidonSponge<Fr, 5, Params5x5Bn254>;
+
+    type TestPoseidonSpongeChip = PoseidonSpongeChip<Fr, 5, Params5x5Bn254>;
+
+    #[derive(Clone)]
+    struct PoseidonTesterConfig {
+        main_gate_config: MainGateConfig,
+        results: Column<Instance>,
+    }
+
+    struct PoseidonTester {
+        domain: u64,
+        inputs: Vec<Option<Fr>>,
+    }
+
+    impl Circuit<Fr> for PoseidonTester {
+        type Config = PoseidonTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                domain: self.domain,
+                inputs: vec![None; self.inputs.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let main_gate_config = MainGate::<Fr>::configure(meta);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                main_gate_config,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.main_gate_config.clone());
+
+            let result = layouter.assign_region(
+                || "poseidon_sponge",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    let mut inputs = Vec::new();
+                    for val in &self.inputs {
+                        inputs.push(main_gate.assign_value(ctx, &UnassignedValue::from(*val))?);
+                    }
+
+                    let mut sponge = TestPoseidonSpongeChip::with_domain(
+                        config.main_gate_config.clone(),
+                        self.domain,
+                    );
+                    sponge.update(&inputs);
+                    sponge.squeeze(ctx)
+                },
+            )?;
+
+            layouter.constrain_instance(result.cell(), config.results, 0)?;
+            Ok(())
+        }
+    }
+
+    /// Absorbs `inputs1`, squeezes `n1` outputs, absorbs `inputs2` and squeezes
+    /// `n2` more.
+    struct DuplexTester {
+        inputs1: Vec<Option<Fr>>,
+        n1: usize,
+        inputs2: Vec<Option<Fr>>,
+        n2: usize,
+    }
+
+    impl Circuit<Fr> for DuplexTester {
+        type Config = PoseidonTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                inputs1: vec![None; self.inputs1.len()],
+                n1: self.n1,
+                inputs2: vec![None; self.inputs2.len()],
+                n2: self.n2,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            PoseidonTester::configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.main_gate_config.clone());
+
+            let outputs = layouter.assign_region(
+                || "poseidon_duplex",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    let mut sponge = TestPoseidonSpongeChip::new(config.main_gate_config.clone());
+
+                    let mut outputs = Vec::new();
+                    for (inputs, n) in [(&self.inputs1, self.n1), (&self.inputs2, self.n2)] {
+                        let mut assigned = Vec::new();
+                        for val in inputs {
+                            let val = main_gate.assign_value(ctx, &UnassignedValue::from(*val))?;
+                            assigned.push(val);
+                        }
+                        sponge.update(&assigned);
+                        outputs.extend(sponge.squeeze_n(ctx, n)?);
+                    }
+                    Ok(outputs)
+                },
+            )?;
+
+            for (i, output) in outputs.iter().enumerate() {
+                layouter.constrain_instance(output.cell(), config.results, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn native_sponge(domain: u64, inputs: &[Fr]) -> Fr {
+        let mut sponge = TestPoseidonSponge::with_domain(domain);
+        sponge.update(inputs);
+        sponge.squeeze()
+    }
+
+    #[test]
+    fn should_match_native_sponge() {
+        let inputs: Vec<Fr> = (0..7u64).map(|n| Fr::from(n)).collect();
+        let native_result = native_sponge(0, &inputs);
+
+        let poseidon_tester = PoseidonTester {
+            domain: 0,
+            inputs: inputs.iter().map(|x| Some(*x)).collect(),
+        };
+
+        let k = 12;
+        let prover = MockProver::run(k, &poseidon_tester, vec![vec![native_result]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn should_match_native_sponge_with_padding_block() {
+        // Two full rate blocks, the padding takes a block of its own.
+        let inputs: Vec<Fr> = (0..8u64).map(|n| Fr::from(n)).collect();
+        let native_result = native_sponge(3, &inputs);
+
+        let poseidon_tester = PoseidonTester {
+            domain: 3,
+            inputs: inputs.iter().map(|x| Some(*x)).collect(),
+        };
+
+        let k = 12;
+        let prover = MockProver::run(k, &poseidon_tester, vec![vec![native_result]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let poseidon_tester = PoseidonTester {
+            domain: 0,
+            inputs: inputs.iter().map(|x| Some(*x)).collect(),
+        };
+        let prover = MockProver::run(k, &poseidon_tester, vec![vec![native_result]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn should_match_native_duplex() {
+        let inputs1: Vec<Fr> = (0..3u64).map(|n| Fr::from(n)).collect();
+        let inputs2: Vec<Fr> = (3..9u64).map(|n| Fr::from(n)).collect();
+
+        let mut sponge = TestPoseidonSponge::new();
+        sponge.update(&inputs1);
+        let mut outputs = sponge.squeeze_n(6);
+        sponge.update(&inputs2);
+        outputs.extend(sponge.squeeze_n(2));
+
+        let duplex_tester = DuplexTester {
+            inputs1: inputs1.iter().map(|x| Some(*x)).collect(),
+            n1: 6,
+            inputs2: inputs2.iter().map(|x| Some(*x)).collect(),
+            n2: 2,
+        };
+
+        let k = 13;
+        let prover = MockProver::run(k, &duplex_tester, vec![outputs]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::poseidon::native::sponge::domain_tag;
+use crate::poseidon::{wrong::PoseidonChip, RoundParams};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::Error;
+use maingate::{AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RegionCtx, Term};
+use std::marker::PhantomData;
+
+/// Hashes the first `len` of `MAX` assigned elements, where `len` is itself a
+/// witness, so messages of any length up to `MAX` share one circuit. The result
+/// is the native `PoseidonSponge` hash of `inputs[..len]` in the same domain.
+///
+/// Every element past `len` is replaced by the `10*` padding, with the one at
+/// position `len`, and all `MAX / RATE + 1` blocks are permuted. Blocks that
+/// start after the padding leave the state as is.
+pub struct PoseidonVarLenChip<F: FieldExt, const WIDTH: usize, const MAX: usize, P>
+where
+    P: RoundParams<F, WIDTH>,
+{
+    main_gate_config: MainGateConfig,
+    domain: u64,
+    _params: PhantomData<P>,
+}
+
+impl<F: FieldExt, const WIDTH: usize, const MAX: usize, P> PoseidonVarLenChip<F, WIDTH, MAX, P>
+where
+    P: RoundParams<F, WIDTH>,
+{
+    pub const RATE: usize = WIDTH - 1;
+
+    pub fn new(main_gate_config: MainGateConfig) -> Self {
+        Self::with_domain(main_gate_config, 0)
+    }
+
+    pub fn with_domain(main_gate_config: MainGateConfig, domain: u64) -> Self {
+        assert!(WIDTH > 1);
+        Self {
+            main_gate_config,
+            domain,
+            _params: PhantomData,
+        }
+    }
+
+    /// Number of blocks absorbed for the longest message.
+    fn num_blocks() -> usize {
+        MAX / Self::RATE + 1
+    }
+
+    pub fn hash(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        inputs: &[AssignedValue<F>; MAX],
+        len: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        let main_gate = MainGate::new(self.main_gate_config.clone());
+        let padded_len = Self::num_blocks() * Self::RATE;
+
+        // `is_len[p] = (p == len)`, exactly one of them is set for `len <= MAX`.
+        let mut is_len: Vec<AssignedValue<F>> = Vec::new();
+        for p in 0..padded_len {
+            let position = main_gate.assign_constant(ctx, F::from(p as u64))?;
+            let is_eq = main_gate.is_equal(ctx, len, &position)?;
+            is_len.push(is_eq.into());
+        }
+        let terms: Vec<Term<F>> = is_len[..=MAX]
+            .iter()
+            .map(|eq| Term::Assigned(eq, F::one()))
+            .collect();
+        let count = main_gate.compose(ctx, &terms, -F::one())?;
+        main_gate.assert_zero(ctx, &count)?;
+
+        // `before[p] = (p < len)`, it drops to zero right at `len`.
+        let mut before: Vec<AssignedValue<F>> = Vec::new();
+        for p in 0..padded_len {
+            let next = match before.last() {
+                Some(prev) => main_gate.sub(ctx, prev, &is_len[p])?,
+                None => {
+                    main_gate.compose(ctx, &[Term::Assigned(&is_len[0], -F::one())], F::one())?
+                }
+            };
+            before.push(next);
+        }
+
+        // Message elements before `len`, the padding one at `len`, zeros after.
+        let mut padded = Vec::new();
+        for p in 0..padded_len {
+            let element = match inputs.get(p) {
+                Some(input) => {
+                    let kept = main_gate.mul(ctx, &before[p], input)?;
+                    main_gate.add(ctx, &kept, &is_len[p])?
+                }
+                None => is_len[p].clone(),
+            };
+            padded.push(element);
+        }
+
+        let zero = main_gate.assign_constant(ctx, F::zero())?;
+        let mut state = [(); WIDTH].map(|_| zero.clone());
+        state[0] = main_gate.assign_constant(ctx, domain_tag(self.domain))?;
+
+        let pos = PoseidonChip::<_, WIDTH, P>::new(self.main_gate_config.clone());
+        for (b, chunk) in padded.chunks(Self::RATE).enumerate() {
+            let start = b * Self::RATE;
+            // The block is absorbed when it starts at or before `len`.
+            let active = main_gate.add(ctx, &before[start], &is_len[start])?;
+
+            let mut input = state.clone();
+            for (i, item) in chunk.iter().enumerate() {
+                input[i + 1] = main_gate.add(ctx, &state[i + 1], item)?;
+            }
+            let next = pos.permute(ctx, input)?;
+
+            for i in 0..WIDTH {
+                let diff = main_gate.sub(ctx, &next[i], &state[i])?;
+                let diff = main_gate.mul(ctx, &active, &diff)?;
+                state[i] = main_gate.add(ctx, &state[i], &diff)?;
+            }
+        }
+
+        Ok(state[1].clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::native::sponge::PoseidonSponge;
+    use crate::poseidon::params::bn254_5x5::Params5x5Bn254;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Column, ConstraintSystem, Instance},
+    };
+    use maingate::UnassignedValue;
+
+    const MAX: usize = 9;
+
+    type TestPoseidonSponge = PoseidonSponge<Fr, 5, Params5x5Bn254>;
+
+    type TestPoseidonVarLenChip = PoseidonVarLenChip<Fr, 5, MAX, Params5x5Bn254>;
+
+    #[derive(Clone)]
+    struct PoseidonTesterConfig {
+        main_gate_config: MainGateConfig,
+        results: Column<Instance>,
+    }
+
+    struct PoseidonTester {
+        inputs: [Option<Fr>; MAX],
+        len: Option<Fr>,
+    }
+
+    impl PoseidonTester {
+        /// Fills the slots past `message` with junk the hash has to ignore.
+        fn new(message: &[Fr], len: u64) -> Self {
+            let mut inputs = [Some(Fr::from(123)); MAX];
+            for (i, item) in message.iter().enumerate() {
+                inputs[i] = Some(*item);
+            }
+            Self {
+                inputs,
+                len: Some(Fr::from(len)),
+            }
+        }
+    }
+
+    impl Circuit<Fr> for PoseidonTester {
+        type Config = PoseidonTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                inputs: [None; MAX],
+                len: None,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let main_gate_config = MainGate::<Fr>::configure(meta);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                main_gate_config,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.main_gate_config.clone());
+
+            let result = layouter.assign_region(
+                || "poseidon_var_len",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    let inputs = self
+                        .inputs
+                        .try_map(|val| main_gate.assign_value(ctx, &UnassignedValue::from(val)))?;
+                    let len = main_gate.assign_value(ctx, &UnassignedValue::from(self.len))?;
+
+                    let chip = TestPoseidonVarLenChip::new(config.main_gate_config.clone());
+                    chip.hash(ctx, &inputs, &len)
+                },
+            )?;
+
+            layouter.constrain_instance(result.cell(), config.results, 0)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_match_native_sponge_for_every_length() {
+        let message: Vec<Fr> = (1..=MAX as u64).map(|n| Fr::from(n)).collect();
+
+        let k = 13;
+        for len in 0..=MAX {
+            let mut sponge = TestPoseidonSponge::new();
+            sponge.update(&message[..len]);
+            let native_result = sponge.squeeze();
+
+            let tester = PoseidonTester::new(&message[..len], len as u64);
+            let prover = MockProver::run(k, &tester, vec![vec![native_result]]).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn should_fail_on_length_over_max() {
+        let message: Vec<Fr> = (1..=MAX as u64).map(|n| Fr::from(n)).collect();
+
+        let mut sponge = TestPoseidonSponge::new();
+        sponge.update(&message);
+        let native_result = sponge.squeeze();
+
+        let tester = PoseidonTester::new(&message, MAX as u64 + 1);
+
+        let k = 13;
+        let prover = MockProver::run(k, &tester, vec![vec![native_result]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use super::params::Poseidon2Params;
+use halo2_proofs::arithmetic::FieldExt;
+use std::marker::PhantomData;
+
+pub struct Poseidon2<F: FieldExt, const WIDTH: usize, P>
+where
+    P: Poseidon2Params<F, WIDTH>,
+{
+    inputs: [F; WIDTH],
+    _params: PhantomData<P>,
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> Poseidon2<F, WIDTH, P>
+where
+    P: Poseidon2Params<F, WIDTH>,
+{
+    pub fn new(inputs: [F; WIDTH]) -> Self {
+        Poseidon2 {
+            inputs,
+            _params: PhantomData,
+        }
+    }
+
+    fn apply_external_mds(state: &[F; WIDTH], mds: &[[F; WIDTH]; WIDTH]) -> [F; WIDTH] {
+        let mut new_state = [F::zero(); WIDTH];
+        for (item, row) in new_state.iter_mut().zip(mds.iter()) {
+            for (mds_ij, s) in row.iter().zip(state.iter()) {
+                *item += *mds_ij * s;
+            }
+        }
+        new_state
+    }
+
+    /// `(1 + diag) * state`, the sum of the lanes added to every scaled lane.
+    fn apply_internal_mds(state: &[F; WIDTH], diag: &[F; WIDTH]) -> [F; WIDTH] {
+        let sum = state.iter().fold(F::zero(), |acc, s| acc + s);
+        let mut new_state = [F::zero(); WIDTH];
+        for ((item, s), d) in new_state.iter_mut().zip(state.iter()).zip(diag.iter()) {
+            *item = *s * d + sum;
+        }
+        new_state
+    }
+
+    fn full_round(state: &[F; WIDTH], round_consts: &[F], mds: &[[F; WIDTH]; WIDTH]) -> [F; WIDTH] {
+        let mut next_state = *state;
+        for (item, round_const) in next_state.iter_mut().zip(round_consts.iter()) {
+            *item = P::sbox_f(*item + round_const);
+        }
+        Self::apply_external_mds(&next_state, mds)
+    }
+
+    pub fn permute(&self) -> [F; WIDTH] {
+        let half_full_rounds = P::full_rounds() / 2;
+        let partial_rounds = P::partial_rounds();
+        let mds = P::mds();
+        let diag = P::internal_diag();
+        let round_constants = P::round_constants();
+
+        let first_round_end = half_full_rounds * WIDTH;
+        let second_round_end = first_round_end + partial_rounds;
+        let first_round_constants = &round_constants[..first_round_end];
+        let second_round_constants = &round_constants[first_round_end..second_round_end];
+        let third_round_constants = &round_constants[second_round_end..];
+
+        let mut state = Self::apply_external_mds(&self.inputs, &mds);
+        for round_consts in first_round_constants.chunks(WIDTH) {
+            state = Self::full_round(&state, round_consts, &mds);
+        }
+
+        for round_const in second_round_constants {
+            state[0] = P::sbox_f(state[0] + round_const);
+            state = Self::apply_internal_mds(&state, &diag);
+        }
+
+        for round_consts in third_round_constants.chunks(WIDTH) {
+            state = Self::full_round(&state, round_consts, &mds);
+        }
+
+        state
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::params::hex_to_field;
+    use crate::poseidon2::params::Params3x5Bn254;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    type TestPoseidon2 = Poseidon2<Fr, 3, Params3x5Bn254>;
+
+    #[test]
+    fn test_native_poseidon2_3x5() {
+        let inputs = [0u64, 1, 2].map(Fr::from);
+
+        // Test vector of the reference implementation.
+        let outputs: [Fr; 3] = [
+            "0x0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033",
+            "0x303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570",
+            "0x1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8",
+        ]
+        .map(|n| hex_to_field(n));
+
+        let poseidon2 = TestPoseidon2::new(inputs);
+
+        assert_eq!(poseidon2.permute(), outputs);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::poseidon::params::{
+    grain::{Grain, SboxType},
+    sbox::Pow5,
+    PolySbox, RoundParams, Sbox,
+};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::pairing::bn256::Fr;
+use halo2_proofs::plonk::{Error, Expression};
+use maingate::{AssignedValue, MainGate, RegionCtx};
+
+/// Parameters of the Poseidon2 permutation. `mds` is the external matrix,
+/// used before the first round and in the full rounds. The internal matrix of
+/// the partial rounds is `1 + diag(internal_diag)`, with `1` the all-ones
+/// matrix.
+///
+/// Partial rounds only have a round constant for lane `0`, so
+/// `round_constants` holds `WIDTH` constants for every full round and one for
+/// every partial round, in the order of the rounds.
+pub trait Poseidon2Params<F: FieldExt, const WIDTH: usize>: RoundParams<F, WIDTH> {
+    fn internal_diag() -> [F; WIDTH];
+}
+
+/// `M4` of the Poseidon2 paper, an MDS matrix of width 4 with small entries.
+const M4: [[u64; 4]; 4] = [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]];
+
+/// External matrix of the Poseidon2 paper: `circ(2, 1)` and `circ(2, 1, 1)`
+/// for widths 2 and 3, `M4` itself for width 4 and `circ(2 * M4, M4, ..., M4)`
+/// for the larger multiples of 4.
+pub fn external_mds<F: FieldExt, const WIDTH: usize>() -> [[F; WIDTH]; WIDTH] {
+    let mut mds = [[F::zero(); WIDTH]; WIDTH];
+    match WIDTH {
+        2 | 3 => {
+            for (i, row) in mds.iter_mut().enumerate() {
+                for (j, item) in row.iter_mut().enumerate() {
+                    *item = F::from(if i == j { 2u64 } else { 1 });
+                }
+            }
+        }
+        4 => {
+            for (row, m4_row) in mds.iter_mut().zip(M4.iter()) {
+                *row = m4_row.map(F::from);
+            }
+        }
+        _ => {
+            assert_eq!(WIDTH % 4, 0, "no Poseidon2 external matrix for this width");
+            for (i, row) in mds.iter_mut().enumerate() {
+                for (j, item) in row.iter_mut().enumerate() {
+                    let block: u64 = if i / 4 == j / 4 { 2 } else { 1 };
+                    *item = F::from(block * M4[i % 4][j % 4]);
+                }
+            }
+        }
+    }
+    mds
+}
+
+/// Round constants of the Poseidon2 reference implementation, drawn from the
+/// same Grain stream as Poseidon's but only one for every partial round.
+pub fn grain_round_constants<F: FieldExt, const WIDTH: usize, P>() -> Vec<F>
+where
+    P: RoundParams<F, WIDTH>,
+{
+    let full_rounds = P::full_rounds();
+    let partial_rounds = P::partial_rounds();
+    let mut grain = Grain::new(P::SBOX, WIDTH, full_rounds, partial_rounds);
+    grain.round_constants(full_rounds * WIDTH + partial_rounds)
+}
+
+/// Poseidon2 over bn254 with width 3, as in the reference implementation:
+/// `x^5`, 8 full and 56 partial rounds.
+pub struct Params3x5Bn254;
+
+impl Sbox for Params3x5Bn254 {
+    const SBOX: SboxType = Pow5::SBOX;
+
+    fn sbox_asgn<F: FieldExt>(
+        main_gate: &MainGate<F>,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        exp: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        Pow5::sbox_asgn(main_gate, ctx, exp)
+    }
+
+    fn sbox_f<F: FieldExt>(f: F) -> F {
+        Pow5::sbox_f(f)
+    }
+}
+
+impl PolySbox for Params3x5Bn254 {
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+        Pow5::sbox_expr(exp)
+    }
+
+    fn sbox_expr_from_square<F: FieldExt>(
+        exp: Expression<F>,
+        square: Expression<F>,
+    ) -> Expression<F> {
+        Pow5::sbox_expr_from_square(exp, square)
+    }
+}
+
+impl RoundParams<Fr, 3> for Params3x5Bn254 {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        56
+    }
+
+    fn round_constants_count() -> usize {
+        Self::full_rounds() * 3 + Self::partial_rounds()
+    }
+
+    fn round_constants() -> Vec<Fr> {
+        grain_round_constants::<Fr, 3, Self>()
+    }
+
+    fn mds() -> [[Fr; 3]; 3] {
+        external_mds()
+    }
+}
+
+impl Poseidon2Params<Fr, 3> for Params3x5Bn254 {
+    fn internal_diag() -> [Fr; 3] {
+        [1u64, 1, 2].map(Fr::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use halo2_proofs::arithmetic::Field;
+
+    /// `M4` times `x` with the additions of the reference implementation.
+    fn reference_m4(x: [Fr; 4]) -> [Fr; 4] {
+        let t0 = x[0] + x[1];
+        let t1 = x[2] + x[3];
+        let t2 = x[1].double() + t1;
+        let t3 = x[3].double() + t0;
+        let t4 = t1.double().double() + t3;
+        let t5 = t0.double().double() + t2;
+        let t6 = t3 + t5;
+        let t7 = t2 + t4;
+        [t6, t5, t7, t4]
+    }
+
+    /// External layer of the reference implementation: `M4` on every chunk of
+    /// 4 lanes, then for widths above 4 the sum of the chunks added to each.
+    fn reference_external<const WIDTH: usize>(x: [Fr; WIDTH]) -> [Fr; WIDTH] {
+        let mut out = x;
+        for chunk in out.chunks_mut(4) {
+            let product = reference_m4(chunk.try_into().unwrap());
+            chunk.copy_from_slice(&product);
+        }
+        if WIDTH > 4 {
+            let mut sums = [Fr::zero(); 4];
+            for chunk in out.chunks(4) {
+                for (sum, lane) in sums.iter_mut().zip(chunk) {
+                    *sum += lane;
+                }
+            }
+            for (i, lane) in out.iter_mut().enumerate() {
+                *lane += sums[i % 4];
+            }
+        }
+        out
+    }
+
+    fn apply<const WIDTH: usize>(mds: &[[Fr; WIDTH]; WIDTH], x: &[Fr; WIDTH]) -> [Fr; WIDTH] {
+        mds.map(|row| {
+            row.iter()
+                .zip(x)
+                .fold(Fr::zero(), |acc, (m, x)| acc + *m * x)
+        })
+    }
+
+    fn check_external_mds<const WIDTH: usize>() {
+        let mut x = [Fr::zero(); WIDTH];
+        for (i, lane) in x.iter_mut().enumerate() {
+            *lane = Fr::from(i as u64 * 7 + 3);
+        }
+        assert_eq!(
+            apply(&external_mds::<Fr, WIDTH>(), &x),
+            reference_external(x)
+        );
+    }
+
+    #[test]
+    fn should_build_external_mds() {
+        let mds = external_mds::<Fr, 8>();
+        // `2 * M4` on the diagonal blocks, `M4` elsewhere.
+        assert_eq!(mds[0][0], Fr::from(10u64));
+        assert_eq!(mds[0][4], Fr::from(5u64));
+        assert_eq!(mds[7][7], Fr::from(12u64));
+        assert_eq!(mds[7][3], Fr::from(6u64));
+
+        // Plain `M4` at width 4, not doubled.
+        let mds = external_mds::<Fr, 4>();
+        let x = [0u64, 1, 2, 3].map(Fr::from);
+        assert_eq!(apply(&mds, &x), [18u64, 11, 34, 27].map(Fr::from));
+
+        let mds = external_mds::<Fr, 3>();
+        assert_eq!(mds[1], [1u64, 2, 1].map(Fr::from));
+    }
+
+    #[test]
+    fn should_match_reference_external_layer() {
+        check_external_mds::<4>();
+        check_external_mds::<8>();
+        check_external_mds::<12>();
+        check_external_mds::<16>();
+    }
+}
@@ -0,0 +1,301 @@
+pub mod native;
+pub mod params;
+pub mod wrong;
+
+use crate::poseidon::params::{PolySbox, WitnessSbox};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Region},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector, VirtualCells},
+    poly::Rotation,
+};
+use params::{inverse_exponent, RescueParams};
+use std::marker::PhantomData;
+
+#[derive(Clone, Debug)]
+pub struct RescueConfig<const WIDTH: usize> {
+    state: [Column<Advice>; WIDTH],
+    /// State after the inverse S-box, in the row of its round.
+    mid_state: [Column<Advice>; WIDTH],
+    forward_constants: [Column<Fixed>; WIDTH],
+    backward_constants: [Column<Fixed>; WIDTH],
+    round_selector: Selector,
+}
+
+/// Rescue-Prime permutation with custom gates, one round to a row. The inverse
+/// S-box `x^(1 / alpha)` has a degree close to the field size, so its output
+/// is witnessed in `mid_state` and checked with the forward S-box instead:
+/// with `u` the state after the first half of the round, the gate constrains
+/// `mid^alpha = u` and `next = mds * mid + constants`.
+pub struct RescueChip<F: FieldExt, const WIDTH: usize, P>
+where
+    P: RescueParams<F, WIDTH> + PolySbox,
+{
+    inputs: [AssignedCell<F, F>; WIDTH],
+    _params: PhantomData<P>,
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> RescueChip<F, WIDTH, P>
+where
+    P: RescueParams<F, WIDTH> + PolySbox,
+{
+    pub fn new(inputs: [AssignedCell<F, F>; WIDTH]) -> Self {
+        RescueChip {
+            inputs,
+            _params: PhantomData,
+        }
+    }
+
+    /// `mds * exprs + constants`
+    fn mds_expr(
+        v_cells: &mut VirtualCells<F>,
+        exprs: &[Expression<F>; WIDTH],
+        mds: &[[F; WIDTH]; WIDTH],
+        constants: &[Column<Fixed>; WIDTH],
+    ) -> [Expression<F>; WIDTH] {
+        let mut new_exprs = constants.map(|column| v_cells.query_fixed(column, Rotation::cur()));
+        for (new_expr, row) in new_exprs.iter_mut().zip(mds.iter()) {
+            for (expr, mds_ij) in exprs.iter().zip(row.iter()) {
+                *new_expr = new_expr.clone() + expr.clone() * Expression::Constant(*mds_ij);
+            }
+        }
+        new_exprs
+    }
+
+    fn apply_mds(
+        state: &[Option<F>; WIDTH],
+        mds: &[[F; WIDTH]; WIDTH],
+        round_consts: &[F],
+    ) -> [Option<F>; WIDTH] {
+        let mut new_state = [None; WIDTH];
+        for ((item, row), round_const) in new_state.iter_mut().zip(mds.iter()).zip(round_consts) {
+            *item = Some(*round_const);
+            for (mds_ij, s) in row.iter().zip(state.iter()) {
+                *item = item.and_then(|a| s.map(|s| a + s * mds_ij));
+            }
+        }
+        new_state
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> RescueConfig<WIDTH> {
+        let state = [(); WIDTH].map(|_| {
+            let column = meta.advice_column();
+            meta.enable_equality(column);
+            column
+        });
+        let mid_state = [(); WIDTH].map(|_| meta.advice_column());
+        let forward_constants = [(); WIDTH].map(|_| meta.fixed_column());
+        let backward_constants = [(); WIDTH].map(|_| meta.fixed_column());
+        let round_selector = meta.selector();
+        let mds = P::mds();
+
+        meta.create_gate("rescue_round", |v_cells| {
+            let s_cells = v_cells.query_selector(round_selector);
+            let exprs =
+                state.map(|column| P::sbox_expr(v_cells.query_advice(column, Rotation::cur())));
+            let forward = Self::mds_expr(v_cells, &exprs, &mds, &forward_constants);
+            let mid = mid_state.map(|column| v_cells.query_advice(column, Rotation::cur()));
+            let backward = Self::mds_expr(v_cells, &mid, &mds, &backward_constants);
+
+            let mut constraints = Vec::new();
+            for (mid, forward) in mid.into_iter().zip(forward) {
+                constraints.extend(P::sbox_constraints(mid, forward));
+            }
+            for (expr, column) in backward.into_iter().zip(state.iter()) {
+                constraints.push(expr - v_cells.query_advice(*column, Rotation::next()));
+            }
+            constraints
+                .into_iter()
+                .map(|constraint| s_cells.clone() * constraint)
+                .collect::<Vec<_>>()
+        });
+
+        RescueConfig {
+            state,
+            mid_state,
+            forward_constants,
+            backward_constants,
+            round_selector,
+        }
+    }
+
+    fn assign_state(
+        columns: &[Column<Advice>; WIDTH],
+        region: &mut Region<'_, F>,
+        row: usize,
+        state: &[Option<F>; WIDTH],
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        let mut state_cells: [Option<AssignedCell<F, F>>; WIDTH] = [(); WIDTH].map(|_| None);
+        for i in 0..WIDTH {
+            state_cells[i] = Some(region.assign_advice(
+                || "state",
+                columns[i],
+                row,
+                || state[i].ok_or(Error::Synthesis),
+            )?);
+        }
+        Ok(state_cells.map(|item| item.unwrap()))
+    }
+
+    pub fn permute(
+        &self,
+        config: &RescueConfig<WIDTH>,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+        let mds = P::mds();
+        let round_constants = P::round_constants();
+        let inverse_exponent = inverse_exponent::<F>(P::alpha());
+
+        layouter.assign_region(
+            || "rescue_rounds",
+            |mut region: Region<'_, F>| {
+                let mut state_cells: [Option<AssignedCell<F, F>>; WIDTH] =
+                    [(); WIDTH].map(|_| None);
+                for i in 0..WIDTH {
+                    let cell =
+                        self.inputs[i].copy_advice(|| "state", &mut region, config.state[i], 0)?;
+                    state_cells[i] = Some(cell);
+                }
+                let mut state_cells = state_cells.map(|item| item.unwrap());
+                let mut next_state = state_cells.clone().map(|cell| cell.value().cloned());
+
+                for (round, round_consts) in round_constants.chunks(2 * WIDTH).enumerate() {
+                    config.round_selector.enable(&mut region, round)?;
+                    let (forward_consts, backward_consts) = round_consts.split_at(WIDTH);
+                    for i in 0..WIDTH {
+                        region.assign_fixed(
+                            || "forward_constant",
+                            config.forward_constants[i],
+                            round,
+                            || Ok(forward_consts[i]),
+                        )?;
+                        region.assign_fixed(
+                            || "backward_constant",
+                            config.backward_constants[i],
+                            round,
+                            || Ok(backward_consts[i]),
+                        )?;
+                    }
+
+                    next_state = next_state.map(|s| s.map(P::sbox_f));
+                    next_state = Self::apply_mds(&next_state, &mds, forward_consts);
+                    let mid_state = next_state.map(|s| s.map(|s| s.pow_vartime(&inverse_exponent)));
+                    Self::assign_state(&config.mid_state, &mut region, round, &mid_state)?;
+                    next_state = Self::apply_mds(&mid_state, &mds, backward_consts);
+
+                    state_cells =
+                        Self::assign_state(&config.state, &mut region, round + 1, &next_state)?;
+                }
+                Ok(state_cells)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::native::RescuePrime;
+    use super::params::Params5x5Bn254;
+    use super::*;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Instance},
+    };
+
+    type TestRescueChip = RescueChip<Fr, 5, Params5x5Bn254>;
+
+    #[derive(Clone)]
+    struct RescueTesterConfig {
+        rescue_config: RescueConfig<5>,
+        temp: Column<Advice>,
+        results: Column<Instance>,
+    }
+
+    struct RescueTester {
+        inputs: [Option<Fr>; 5],
+    }
+
+    impl Circuit<Fr> for RescueTester {
+        type Config = RescueTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self { inputs: [None; 5] }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let rescue_config = TestRescueChip::configure(meta);
+            let temp = meta.advice_column();
+            let results = meta.instance_column();
+
+            meta.enable_equality(temp);
+            meta.enable_equality(results);
+
+            Self::Config {
+                rescue_config,
+                temp,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let init_state = layouter.assign_region(
+                || "load_state",
+                |mut region: Region<'_, Fr>| {
+                    let mut state: [Option<AssignedCell<Fr, Fr>>; 5] = [(); 5].map(|_| None);
+                    for i in 0..5 {
+                        state[i] = Some(region.assign_advice(
+                            || "state",
+                            config.temp,
+                            i,
+                            || self.inputs[i].ok_or(Error::Synthesis),
+                        )?);
+                    }
+                    Ok(state.map(|item| item.unwrap()))
+                },
+            )?;
+
+            let rescue = TestRescueChip::new(init_state);
+            let result_state =
+                rescue.permute(&config.rescue_config, layouter.namespace(|| "rescue"))?;
+            for i in 0..5 {
+                layouter.constrain_instance(result_state[i].cell(), config.results, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rescue_prime_5x5() {
+        let inputs = [0u64, 1, 2, 3, 4].map(Fr::from);
+        let outputs = RescuePrime::<Fr, 5, Params5x5Bn254>::new(inputs).permute();
+
+        let rescue_tester = RescueTester {
+            inputs: inputs.map(Some),
+        };
+
+        let k = 5;
+        let prover = MockProver::run(k, &rescue_tester, vec![outputs.to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_rescue_prime_5x5_wrong_input() {
+        let inputs = [0u64, 1, 2, 3, 4].map(Fr::from);
+        let outputs = RescuePrime::<Fr, 5, Params5x5Bn254>::new(inputs).permute();
+
+        let rescue_tester = RescueTester {
+            inputs: [5u64, 1, 2, 3, 4].map(|n| Some(Fr::from(n))),
+        };
+
+        let k = 5;
+        let prover = MockProver::run(k, &rescue_tester, vec![outputs.to_vec()]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use super::params::{inverse_exponent, RescueParams};
+use halo2_proofs::arithmetic::FieldExt;
+use std::marker::PhantomData;
+
+pub struct RescuePrime<F: FieldExt, const WIDTH: usize, P>
+where
+    P: RescueParams<F, WIDTH>,
+{
+    inputs: [F; WIDTH],
+    _params: PhantomData<P>,
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> RescuePrime<F, WIDTH, P>
+where
+    P: RescueParams<F, WIDTH>,
+{
+    pub fn new(inputs: [F; WIDTH]) -> Self {
+        RescuePrime {
+            inputs,
+            _params: PhantomData,
+        }
+    }
+
+    /// `mds * state + round_consts`
+    fn apply_mds(state: &[F; WIDTH], mds: &[[F; WIDTH]; WIDTH], round_consts: &[F]) -> [F; WIDTH] {
+        let mut new_state = [F::zero(); WIDTH];
+        for ((item, row), round_const) in new_state.iter_mut().zip(mds.iter()).zip(round_consts) {
+            for (mds_ij, s) in row.iter().zip(state.iter()) {
+                *item += *mds_ij * s;
+            }
+            *item += round_const;
+        }
+        new_state
+    }
+
+    pub fn permute(&self) -> [F; WIDTH] {
+        let mds = P::mds();
+        let round_constants = P::round_constants();
+        let inverse_exponent = inverse_exponent::<F>(P::alpha());
+
+        let mut state = self.inputs;
+        for round_consts in round_constants.chunks(2 * WIDTH) {
+            let (forward_consts, backward_consts) = round_consts.split_at(WIDTH);
+            state = state.map(P::sbox_f);
+            state = Self::apply_mds(&state, &mds, forward_consts);
+            state = state.map(|s| s.pow_vartime(&inverse_exponent));
+            state = Self::apply_mds(&state, &mds, backward_consts);
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::params::hex_to_field;
+    use crate::rescue::params::Params5x5Bn254;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    type TestRescuePrime = RescuePrime<Fr, 5, Params5x5Bn254>;
+
+    #[test]
+    fn test_native_rescue_prime_5x5() {
+        let inputs = [0u64, 1, 2, 3, 4].map(Fr::from);
+
+        // Output of the Rescue-Prime reference with the same parameters.
+        let outputs: [Fr; 5] = [
+            "0x2cd8fc0509157f1faf9f730aeb40f6eb745872e281675e39a54b12f60b504dae",
+            "0x2888c57a46a166001bd4d59c6ee0996a952fd7321fd1f79dacb31b4729977743",
+            "0x1fd755076447d23085a92725eb0e9009678be249c091feb5a534fef9a6424402",
+            "0x2ee747db42419d1dff124db129c597a15857404c519a595ade6ab8c83fe3fa8f",
+            "0x0c3600f7a922d59314a27b26130c152b346c1f4372e9deef67acd6be36b5cd3d",
+        ]
+        .map(|n| hex_to_field(n));
+
+        assert_eq!(TestRescuePrime::new(inputs).permute(), outputs);
+    }
+}
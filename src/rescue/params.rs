@@ -0,0 +1,324 @@
+use crate::poseidon::params::{grain::SboxType, sbox::Pow5, PolySbox, Sbox};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::pairing::bn256::Fr;
+use halo2_proofs::plonk::{Error, Expression};
+use maingate::{AssignedValue, MainGate, RegionCtx};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+
+/// Parameters of the Rescue-Prime permutation. Every round applies the S-box
+/// `x^alpha` of `Sbox`, the MDS matrix and `WIDTH` round constants, then the
+/// inverse S-box `x^(1 / alpha)`, the MDS matrix and `WIDTH` more constants,
+/// so `round_constants` holds `2 * WIDTH` constants for every round.
+pub trait RescueParams<F: FieldExt, const WIDTH: usize>: Sbox {
+    /// Exponent of the S-box. It must be coprime to `p - 1`.
+    fn alpha() -> u64;
+    fn rounds() -> usize;
+
+    fn round_constants_count() -> usize {
+        2 * WIDTH * Self::rounds()
+    }
+
+    fn round_constants() -> Vec<F>;
+    fn mds() -> [[F; WIDTH]; WIDTH];
+}
+
+/// `p - 1` in little-endian limbs.
+fn order_limbs<F: FieldExt>() -> Vec<u64> {
+    let repr = (-F::one()).to_repr();
+    repr.as_ref()
+        .chunks(8)
+        .map(|chunk| {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            u64::from_le_bytes(bytes)
+        })
+        .collect()
+}
+
+/// `d` with `alpha * d = 1 mod p - 1`, so that `x^d` inverts `x^alpha`, in
+/// little-endian limbs for `pow_vartime`.
+pub fn inverse_exponent<F: FieldExt>(alpha: u64) -> Vec<u64> {
+    let mut order = order_limbs::<F>();
+    // Room for the carry of `k * (p - 1) + 1`.
+    order.push(0);
+
+    // `d = (k * (p - 1) + 1) / alpha` for the `k < alpha` making it exact.
+    for k in 1..alpha {
+        let mut carry = 1u128;
+        let multiple: Vec<u64> = order
+            .iter()
+            .map(|limb| {
+                let value = *limb as u128 * k as u128 + carry;
+                carry = value >> 64;
+                value as u64
+            })
+            .collect();
+
+        let mut remainder = 0u128;
+        let mut quotient = vec![0u64; multiple.len()];
+        for (q, limb) in quotient.iter_mut().zip(multiple.iter()).rev() {
+            let value = (remainder << 64) | *limb as u128;
+            *q = (value / alpha as u128) as u64;
+            remainder = value % alpha as u128;
+        }
+        if remainder == 0 {
+            return quotient;
+        }
+    }
+    panic!("alpha is not coprime to p - 1")
+}
+
+/// Rounds of Rescue-Prime at `security` bits, as `get_number_of_rounds` of the
+/// reference implementation: the least number of rounds `l1` making a Groebner
+/// basis attack cost more than `2^security`, with a 50% margin over
+/// `max(5, l1)`.
+pub fn rescue_rounds(width: usize, capacity: usize, alpha: u64, security: usize) -> usize {
+    let rate = width - capacity;
+    let log2_binomial = |n: usize, k: usize| -> f64 {
+        (0..k)
+            .map(|i| ((n - i) as f64 / (k - i) as f64).log2())
+            .sum()
+    };
+    let l1 = (1..25)
+        .find(|l1| {
+            let dcon = (0.5 * (alpha - 1) as f64 * width as f64 * (l1 - 1) as f64 + 2.0).floor();
+            let v = width * (l1 - 1) + rate;
+            2.0 * log2_binomial(v + dcon as usize, v) > security as f64
+        })
+        .expect("no round number up to 24 is secure");
+    (1.5 * l1.max(5) as f64).ceil() as usize
+}
+
+/// Decimal digits of `p`, the way the reference writes it in its seed.
+fn modulus_decimal<F: FieldExt>() -> String {
+    let mut limbs = order_limbs::<F>();
+    // `p - 1` is even, so adding one does not carry.
+    limbs[0] += 1;
+
+    let mut digits = Vec::new();
+    while limbs.iter().any(|limb| *limb != 0) {
+        let mut remainder = 0u128;
+        for limb in limbs.iter_mut().rev() {
+            let value = (remainder << 64) | *limb as u128;
+            *limb = (value / 10) as u64;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+/// Round constants of the Rescue-Prime reference: the SHAKE256 stream of the
+/// seed `Rescue-XLIX(p,width,capacity,security)`, read in chunks one byte
+/// longer than `p` and reduced as little-endian integers.
+pub fn reference_round_constants<F: FieldExt>(
+    width: usize,
+    capacity: usize,
+    security: usize,
+    rounds: usize,
+) -> Vec<F> {
+    let bytes_per_int = (F::NUM_BITS as usize + 7) / 8 + 1;
+    let seed = format!(
+        "Rescue-XLIX({},{},{},{})",
+        modulus_decimal::<F>(),
+        width,
+        capacity,
+        security
+    );
+
+    let mut shake = Shake256::default();
+    shake.update(seed.as_bytes());
+    let mut reader = shake.finalize_xof();
+    (0..2 * width * rounds)
+        .map(|_| {
+            let mut bytes = [0u8; 64];
+            reader.read(&mut bytes[..bytes_per_int]);
+            F::from_bytes_wide(&bytes)
+        })
+        .collect()
+}
+
+/// MDS matrix of the Rescue-Prime reference: the transposed right half of the
+/// reduced echelon form of the `WIDTH x 2 * WIDTH` matrix `g^(i * j)`, where
+/// `generator` is the smallest primitive element `g` of the field.
+pub fn reference_mds<F: FieldExt, const WIDTH: usize>(generator: F) -> [[F; WIDTH]; WIDTH] {
+    let mut rows: Vec<Vec<F>> = (0..WIDTH)
+        .map(|i| {
+            (0..2 * WIDTH)
+                .map(|j| generator.pow_vartime(&[(i * j) as u64]))
+                .collect()
+        })
+        .collect();
+
+    for col in 0..WIDTH {
+        let pivot = (col..WIDTH)
+            .find(|&row| rows[row][col] != F::zero())
+            .expect("the powers of the generator are not distinct");
+        rows.swap(col, pivot);
+
+        let inverse = rows[col][col].invert().unwrap();
+        for item in rows[col].iter_mut() {
+            *item *= inverse;
+        }
+        let pivot_row = rows[col].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i == col {
+                continue;
+            }
+            let factor = row[col];
+            for (item, pivot_item) in row.iter_mut().zip(pivot_row.iter()) {
+                *item -= factor * pivot_item;
+            }
+        }
+    }
+
+    let mut mds = [[F::zero(); WIDTH]; WIDTH];
+    for (i, row) in mds.iter_mut().enumerate() {
+        for (j, item) in row.iter_mut().enumerate() {
+            *item = rows[j][WIDTH + i];
+        }
+    }
+    mds
+}
+
+/// Rescue-Prime over bn254 with width 5 and capacity 1: `x^5` and 9 rounds at
+/// 128 bits, see `rescue_rounds`. The round constants and the MDS matrix are
+/// those of the Rescue-Prime reference, with `5` the smallest primitive
+/// element of the field.
+pub struct Params5x5Bn254;
+
+impl Sbox for Params5x5Bn254 {
+    const SBOX: SboxType = Pow5::SBOX;
+
+    fn sbox_asgn<F: FieldExt>(
+        main_gate: &MainGate<F>,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        exp: &AssignedValue<F>,
+    ) -> Result<AssignedValue<F>, Error> {
+        Pow5::sbox_asgn(main_gate, ctx, exp)
+    }
+
+    fn sbox_f<F: FieldExt>(f: F) -> F {
+        Pow5::sbox_f(f)
+    }
+}
+
+impl PolySbox for Params5x5Bn254 {
+    fn sbox_expr<F: FieldExt>(exp: Expression<F>) -> Expression<F> {
+        Pow5::sbox_expr(exp)
+    }
+}
+
+impl RescueParams<Fr, 5> for Params5x5Bn254 {
+    fn alpha() -> u64 {
+        5
+    }
+
+    fn rounds() -> usize {
+        9
+    }
+
+    fn round_constants() -> Vec<Fr> {
+        reference_round_constants(5, 1, 128, Self::rounds())
+    }
+
+    fn mds() -> [[Fr; 5]; 5] {
+        reference_mds(Fr::from(5u64))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::poseidon::params::hex_to_field;
+    use halo2_proofs::arithmetic::Field;
+
+    #[test]
+    fn should_match_reference_round_numbers() {
+        assert_eq!(rescue_rounds(3, 1, 5, 128), 14);
+        assert_eq!(rescue_rounds(4, 1, 5, 128), 11);
+        assert_eq!(rescue_rounds(5, 1, 5, 128), Params5x5Bn254::rounds());
+        assert_eq!(rescue_rounds(6, 1, 5, 128), 8);
+    }
+
+    #[test]
+    fn should_invert_sbox() {
+        let exponent = inverse_exponent::<Fr>(Params5x5Bn254::alpha());
+        for x in [Fr::zero(), Fr::one(), Fr::from(7u64), -Fr::from(3u64)] {
+            let root = x.pow_vartime(&exponent);
+            assert_eq!(Params5x5Bn254::sbox_f(root), x);
+            assert_eq!(Params5x5Bn254::sbox_f(x).pow_vartime(&exponent), x);
+        }
+    }
+
+    #[test]
+    fn should_match_reference_constants() {
+        assert_eq!(
+            modulus_decimal::<Fr>(),
+            "21888242871839275222246405745257275088548364400416034343698204186575808495617"
+        );
+
+        let round_constants = Params5x5Bn254::round_constants();
+        assert_eq!(
+            round_constants[0],
+            hex_to_field("0x17da3d2b361cb0f798b206de10b9863774ab15469fdac3e0752dc5a00a341b28")
+        );
+        assert_eq!(
+            round_constants[1],
+            hex_to_field("0x1eb22ca4a666148be7e9af480cc37a7a2c04a42e2a0c09909bafcced16b473ea")
+        );
+        assert_eq!(
+            round_constants[89],
+            hex_to_field("0x1eacbaf590deb53f07ffbdbf2bb74a5861249f824b3177d60438aa413dc8555f")
+        );
+    }
+
+    #[test]
+    fn should_match_reference_mds() {
+        // `2`, `3` and `4` are squares, so `5` is the first candidate that
+        // can be primitive.
+        let order = order_limbs::<Fr>();
+        let half_order: Vec<u64> = (0..order.len())
+            .map(|i| (order[i] >> 1) | (order.get(i + 1).unwrap_or(&0) << 63))
+            .collect();
+        for x in [2u64, 3, 4] {
+            assert_eq!(Fr::from(x).pow_vartime(&half_order), Fr::one());
+        }
+        assert_eq!(Fr::from(5u64).pow_vartime(&half_order), -Fr::one());
+
+        let mds = Params5x5Bn254::mds();
+        assert_eq!(
+            mds[0],
+            [
+                "0x9502f9",
+                "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593ef45cb8c",
+                "0x26bb0a",
+                "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593effe7367",
+                "0x030d",
+            ]
+            .map(|n| hex_to_field(n))
+        );
+        assert_eq!(
+            mds[4],
+            [
+                "0x1b2d1e4e41a42996",
+                "0x30644e72e131a029b85045b68181585d2833e84879b9709121f7753030f75ea6",
+                "0x070266eaf4153b9c",
+                "0x30644e72e131a029b85045b68181585d2833e84879b97091439c7eb8e0f1167d",
+                "0x7205985e25ae",
+            ]
+            .map(|n| hex_to_field(n))
+        );
+    }
+
+    #[test]
+    fn should_draw_distinct_constants() {
+        let round_constants = Params5x5Bn254::round_constants();
+        assert_eq!(round_constants.len(), 2 * 5 * 9);
+        for (i, constant) in round_constants.iter().enumerate() {
+            assert!(!round_constants[i + 1..].contains(constant));
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use super::params::{inverse_exponent, RescueParams};
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::Error;
+use maingate::{
+    AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RegionCtx, Term, UnassignedValue,
+};
+use std::marker::PhantomData;
+
+/// Rescue-Prime permutation expressed purely with `MainGate` operations, the
+/// counterpart of `poseidon::wrong::PoseidonChip`. The inverse S-box is
+/// witnessed and checked by raising it to `alpha`.
+pub struct RescueChip<F: FieldExt, const WIDTH: usize, P>
+where
+    P: RescueParams<F, WIDTH>,
+{
+    main_gate: MainGate<F>,
+    _params: PhantomData<P>,
+}
+
+impl<F: FieldExt, const WIDTH: usize, P> RescueChip<F, WIDTH, P>
+where
+    P: RescueParams<F, WIDTH>,
+{
+    pub fn new(main_gate_config: MainGateConfig) -> Self {
+        RescueChip {
+            main_gate: MainGate::new(main_gate_config),
+            _params: PhantomData,
+        }
+    }
+
+    /// `mds * state + round_consts`
+    fn apply_mds(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        state: &[AssignedValue<F>; WIDTH],
+        mds: &[[F; WIDTH]; WIDTH],
+        round_consts: &[F],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        let mut new_state = state.clone();
+        for i in 0..WIDTH {
+            let terms: Vec<Term<F>> = state
+                .iter()
+                .zip(mds[i])
+                .map(|(s, mds_ij)| Term::Assigned(s, mds_ij))
+                .collect();
+            new_state[i] = self.main_gate.compose(ctx, &terms, round_consts[i])?;
+        }
+        Ok(new_state)
+    }
+
+    /// `x^(1 / alpha)`, constrained by `root^alpha = x`. The S-box is a
+    /// permutation, so the root is unique.
+    fn inverse_sbox_asgn(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        x: &AssignedValue<F>,
+        inverse_exponent: &[u64],
+    ) -> Result<AssignedValue<F>, Error> {
+        let root = x.value().map(|x| x.pow_vartime(inverse_exponent));
+        let root = self
+            .main_gate
+            .assign_value(ctx, &UnassignedValue::from(root))?;
+        let power = P::sbox_asgn(&self.main_gate, ctx, &root)?;
+        self.main_gate.assert_equal(ctx, &power, x)?;
+        Ok(root)
+    }
+
+    pub fn permute(
+        &self,
+        ctx: &mut RegionCtx<'_, '_, F>,
+        inputs: [AssignedValue<F>; WIDTH],
+    ) -> Result<[AssignedValue<F>; WIDTH], Error> {
+        let mds = P::mds();
+        let round_constants = P::round_constants();
+        let inverse_exponent = inverse_exponent::<F>(P::alpha());
+
+        let mut state = inputs;
+        for round_consts in round_constants.chunks(2 * WIDTH) {
+            let (forward_consts, backward_consts) = round_consts.split_at(WIDTH);
+            for i in 0..WIDTH {
+                state[i] = P::sbox_asgn(&self.main_gate, ctx, &state[i])?;
+            }
+            state = self.apply_mds(ctx, &state, &mds, forward_consts)?;
+            for i in 0..WIDTH {
+                state[i] = self.inverse_sbox_asgn(ctx, &state[i], &inverse_exponent)?;
+            }
+            state = self.apply_mds(ctx, &state, &mds, backward_consts)?;
+        }
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rescue::native::RescuePrime;
+    use crate::rescue::params::Params5x5Bn254;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pairing::bn256::Fr,
+        plonk::{Circuit, Column, ConstraintSystem, Instance},
+    };
+
+    type TestRescueChip = RescueChip<Fr, 5, Params5x5Bn254>;
+
+    #[derive(Clone)]
+    struct RescueTesterConfig {
+        main_gate_config: MainGateConfig,
+        results: Column<Instance>,
+    }
+
+    struct RescueTester {
+        inputs: [Option<Fr>; 5],
+    }
+
+    impl Circuit<Fr> for RescueTester {
+        type Config = RescueTesterConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self { inputs: [None; 5] }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let main_gate_config = MainGate::<Fr>::configure(meta);
+            let results = meta.instance_column();
+
+            meta.enable_equality(results);
+
+            Self::Config {
+                main_gate_config,
+                results,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let main_gate = MainGate::<Fr>::new(config.main_gate_config.clone());
+            let rescue = TestRescueChip::new(config.main_gate_config);
+
+            let result_state = layouter.assign_region(
+                || "rescue",
+                |mut region| {
+                    let position = &mut 0;
+                    let ctx = &mut RegionCtx::new(&mut region, position);
+                    let inputs = self
+                        .inputs
+                        .try_map(|val| main_gate.assign_value(ctx, &UnassignedValue::from(val)))?;
+                    rescue.permute(ctx, inputs)
+                },
+            )?;
+
+            for i in 0..5 {
+                layouter.constrain_instance(result_state[i].cell(), config.results, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_match_native_rescue_prime_5x5() {
+        let inputs = [0u64, 1, 2, 3, 4].map(Fr::from);
+        let outputs = RescuePrime::<Fr, 5, Params5x5Bn254>::new(inputs).permute();
+
+        let rescue_tester = RescueTester {
+            inputs: inputs.map(Some),
+        };
+
+        let k = 11;
+        let prover = MockProver::run(k, &rescue_tester, vec![outputs.to_vec()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let rescue_tester = RescueTester {
+            inputs: [5u64, 1, 2, 3, 4].map(|n| Some(Fr::from(n))),
+        };
+        let prover = MockProver::run(k, &rescue_tester, vec![outputs.to_vec()]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}